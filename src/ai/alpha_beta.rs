@@ -0,0 +1,277 @@
+use std::time::Instant;
+
+use game::{AI, EmptyPersistentState, Order, Player, Owned};
+use game::evaluation::{Evaluation, Material};
+use game_view::{GameView, Simulation};
+use ai::budget::Budget;
+
+// Orders of all units of one side for one cycle
+type Action<'p> = Vec<Owned<'p, Order>>;
+
+// Position within a cycle: which side chooses first this cycle and whether the second side is
+// choosing now
+#[derive(Debug, Clone, Copy)]
+struct Turn {
+    me_first: bool,
+    second: bool,
+}
+
+impl Turn {
+    fn is_mine(&self) -> bool {
+        self.me_first != self.second
+    }
+
+    // Sides alternate in going first every cycle
+    fn next(&self) -> Turn {
+        if self.second {
+            Turn { me_first: !self.me_first, second: false }
+        } else {
+            Turn { me_first: self.me_first, second: true }
+        }
+    }
+}
+
+struct Search<'p, 'c> {
+    player: &'p Player,
+    enemies: Vec<&'p Player>,
+    config: &'c AlphaBeta,
+    started: Instant,
+    // Time is not checked until the first depth is searched so there is always an action to
+    // return
+    timed: bool,
+}
+
+impl<'p, 'c> Search<'p, 'c> {
+    // Combinations of orders for units of given players; each unit can also wait, which is tried
+    // first so that the first action is always to give no orders
+    fn actions<'m>(&self, simulation: &Simulation<'p, 'm>, players: &[&'p Player]) -> Vec<Action<'p>> {
+        let mut units = Vec::new();
+        for &player in players {
+            for (_entity_id, orders) in simulation.view_for(player).all_legal_orders() {
+                units.push((player, orders));
+            }
+        }
+
+        // Counting over options of every unit; first unit changes fastest
+        let mut choices = vec![0; units.len()];
+        let mut actions = Vec::new();
+        loop {
+            actions.push(
+                units.iter()
+                    .zip(&choices)
+                    .filter(|&(_, &choice)| choice > 0)
+                    .map(|(&(player, ref orders), &choice)| Owned(player, orders[choice - 1]))
+                    .collect()
+            );
+            if actions.len() >= self.config.max_actions {
+                return actions;
+            }
+
+            let mut position = 0;
+            loop {
+                if position == units.len() {
+                    return actions;
+                }
+                choices[position] += 1;
+                if choices[position] <= units[position].1.len() {
+                    break;
+                }
+                choices[position] = 0;
+                position += 1;
+            }
+        }
+    }
+
+    fn evaluate(&self, simulation: &Simulation<'p, '_>) -> f64 {
+        self.config.evaluation.evaluate(&simulation.view_for(self.player))
+    }
+
+    // Value of the position from my point of view or None if time ran out and search is timed. Within a cycle one
+    // side chooses its action first and the other side responds to it; both actions are then
+    // applied together. Which side goes first alternates every cycle.
+    fn alpha_beta<'m>(
+        &self,
+        simulation: &Simulation<'p, 'm>,
+        pending: &[Owned<'p, Order>],
+        turn: Turn,
+        depth: usize,
+        mut alpha: f64,
+        mut beta: f64,
+    ) -> Option<f64> {
+        if self.timed && self.config.budget.out_of_time(self.started) {
+            return None;
+        }
+        if depth == 0 {
+            // Depth is always whole cycles so no action is left pending here
+            debug_assert!(pending.is_empty());
+            return Some(self.evaluate(simulation));
+        }
+
+        let my_turn = turn.is_mine();
+        let actions = if my_turn {
+            self.actions(simulation, &[self.player])
+        } else {
+            self.actions(simulation, &self.enemies)
+        };
+
+        let mut best = if my_turn { f64::NEG_INFINITY } else { f64::INFINITY };
+        for action in actions {
+            let value = if turn.second {
+                let mut next = simulation.clone();
+                next.apply(pending.iter().cloned().chain(action));
+                self.alpha_beta(&next, &[], turn.next(), depth - 1, alpha, beta)?
+            } else {
+                self.alpha_beta(simulation, &action, turn.next(), depth - 1, alpha, beta)?
+            };
+
+            if my_turn {
+                best = best.max(value);
+                alpha = alpha.max(best);
+            } else {
+                best = best.min(value);
+                beta = beta.min(best);
+            }
+            if alpha >= beta {
+                break;
+            }
+        }
+
+        Some(best)
+    }
+
+    // Best of my actions searched to given depth; previously best action is searched first to
+    // prune more
+    fn root<'m>(
+        &self,
+        simulation: &Simulation<'p, 'm>,
+        depth: usize,
+        previous: Option<&Action<'p>>,
+    ) -> Option<(Action<'p>, f64)> {
+        let mut actions = self.actions(simulation, &[self.player]);
+        if let Some(previous) = previous {
+            if let Some(position) = actions.iter().position(|action| same_action(action, previous)) {
+                let action = actions.remove(position);
+                actions.insert(0, action);
+            }
+        }
+
+        let mut best: Option<(Action<'p>, f64)> = None;
+        for action in actions {
+            let alpha = best.as_ref().map(|&(_, value)| value).unwrap_or(f64::NEG_INFINITY);
+            let turn = Turn { me_first: true, second: true };
+            let value = self.alpha_beta(simulation, &action, turn, depth - 1, alpha, f64::INFINITY)?;
+            if best.as_ref().map(|&(_, best)| value > best).unwrap_or(true) {
+                best = Some((action, value));
+            }
+        }
+        best
+    }
+}
+
+fn same_action(a: &[Owned<Order>], b: &[Owned<Order>]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).all(|(a, b)| a.1 == b.1)
+}
+
+// Alpha-beta search over cloned game states in the spirit of ABCD (alpha-beta considering
+// durations). Moves in a cycle are simultaneous so each cycle is searched as two plies: one side
+// commits to its orders and the other responds. I go first at the root, which makes the choice
+// pessimistic, and sides alternate on following cycles. Units busy with durative moves have no
+// legal orders, so a side with only busy units has just the option to wait.
+//
+// Combinations of orders grow exponentially with number of units so only max_actions of them
+// are tried per ply. Search is deepened one cycle (two plies) at a time until budget runs out, so
+// both sides' actions are always applied before evaluation; budget iterations limit the depth in
+// cycles.
+pub struct AlphaBeta {
+    pub budget: Budget,
+    pub max_actions: usize,
+    pub evaluation: Box<dyn Evaluation + Send>,
+}
+
+impl Default for AlphaBeta {
+    fn default() -> AlphaBeta {
+        AlphaBeta {
+            budget: Budget::default(),
+            max_actions: 32,
+            evaluation: Box::new(Material),
+        }
+    }
+}
+
+impl AI for AlphaBeta {
+    type PersistentState = EmptyPersistentState;
+    fn update<'p: 'gs, 's: 'gs, 't: 'gs, 'gs>(
+        &mut self,
+        _sate: &'s mut Self::PersistentState,
+        view: GameView<'p, 't, 'gs>,
+    ) -> Vec<Order> {
+        let mut search = Search {
+            player: view.player,
+            enemies: view.enemies(),
+            config: self,
+            started: Instant::now(),
+            timed: false,
+        };
+        let simulation = view.simulation();
+
+        let mut best: Option<Action> = None;
+        let mut cycles = 1;
+        while let Some((action, _value)) = search.root(&simulation, 2 * cycles, best.as_ref()) {
+            best = Some(action);
+            search.timed = true;
+            if self.budget.exhausted(cycles, search.started) {
+                break;
+            }
+            cycles += 1;
+        }
+
+        best.map(|action| action.into_iter().map(|Owned(_, order)| order).collect())
+            .unwrap_or_default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+    use game::{Coordinates, Unit, Object, Direction};
+    use game::test_support::TwoPlayers;
+
+    #[test]
+    fn alpha_beta_follows_evaluation() {
+        let players = TwoPlayers::default();
+        let (p1, p2) = (&players.p1, &players.p2);
+
+        let game = players.game(&[
+            (Coordinates(3, 0), Object::Unit(p1, Unit::Worker)),
+            (Coordinates(6, 6), Object::Unit(p2, Unit::Worker)),
+        ]);
+
+        // My worker wants to get to top left corner and enemy worker wants to stop it by getting
+        // there first
+        fn race(view: &GameView) -> f64 {
+            let distance = |mine| {
+                view.entities()
+                    .find(|&(_, entity)| view.is_mine(entity) == mine)
+                    .map(|(_, entity)| entity.location.coordinates.manhattan_distance(Coordinates(0, 0)) as f64)
+                    .unwrap()
+            };
+            distance(false) - distance(true)
+        }
+
+        let mut ai = AlphaBeta {
+            budget: Budget { iterations: Some(2), time: None },
+            evaluation: Box::new(race),
+            ..Default::default()
+        };
+
+        let worker = game.view_for(p1).my_units().next().unwrap().entity_id;
+        let orders = ai.update(&mut EmptyPersistentState, game.view_for(p1));
+        assert_eq!(orders, vec![Order::Move(worker, Direction::Left)]);
+
+        // First cycle is searched even with no time at all
+        ai.budget = Budget { iterations: None, time: Some(Duration::from_secs(0)) };
+        let orders = ai.update(&mut EmptyPersistentState, game.view_for(p1));
+        assert_eq!(orders, vec![Order::Move(worker, Direction::Left)]);
+    }
+}
@@ -0,0 +1,28 @@
+use std::time::{Duration, Instant};
+
+// Limits search; it stops when any of the set limits is reached but always does at least one
+// iteration
+#[derive(Debug, Clone, Copy)]
+pub struct Budget {
+    pub iterations: Option<usize>,
+    pub time: Option<Duration>,
+}
+
+impl Default for Budget {
+    fn default() -> Budget {
+        Budget {
+            iterations: None,
+            time: Some(Duration::from_millis(100)),
+        }
+    }
+}
+
+impl Budget {
+    pub fn exhausted(&self, iterations: usize, started: Instant) -> bool {
+        self.iterations.map(|limit| iterations >= limit).unwrap_or(false) || self.out_of_time(started)
+    }
+
+    pub fn out_of_time(&self, started: Instant) -> bool {
+        self.time.map(|limit| started.elapsed() >= limit).unwrap_or(false)
+    }
+}
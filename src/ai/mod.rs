@@ -1,2 +1,8 @@
+pub mod alpha_beta;
+pub mod budget;
 pub mod idle_ai;
+pub mod naive_mcts;
+pub mod portfolio;
+pub mod random_ai;
+pub mod rush_ai;
 pub mod test_ai;
@@ -0,0 +1,274 @@
+use std::time::Instant;
+
+use rand::{Rng, XorShiftRng};
+
+use game::{AI, EmptyPersistentState, Order, EntityID, Player, Owned};
+use game::evaluation::{Evaluation, Material};
+use game_view::{GameView, Simulation};
+use ai::random_ai::{RandomBiasedAI, seeded_rng, random_seed};
+use ai::budget::Budget;
+
+// Statistics of one option of a unit or one child node
+#[derive(Debug, Clone, Copy, Default)]
+struct Arm {
+    visits: u32,
+    value: f64,
+}
+
+impl Arm {
+    fn mean(&self) -> f64 {
+        if self.visits == 0 { 0.0 } else { self.value / self.visits as f64 }
+    }
+
+    fn update(&mut self, reward: f64) {
+        self.visits += 1;
+        self.value += reward;
+    }
+}
+
+struct Node<'p, 'm> {
+    simulation: Simulation<'p, 'm>,
+    // Legal orders of my units; option 0 of each unit is to give no order
+    units: Vec<(EntityID, Vec<Order>)>,
+    // Local bandit for every unit's options
+    local: Vec<Vec<Arm>>,
+    children: Vec<(Vec<usize>, Arm, Node<'p, 'm>)>,
+}
+
+// Search state shared by all nodes
+struct Search<'p, P: AI> {
+    player: &'p Player,
+    enemies: Vec<&'p Player>,
+    policy: P,
+    policy_state: P::PersistentState,
+    rng: XorShiftRng,
+}
+
+impl<'p, P: AI> Search<'p, P> {
+    fn policy_orders<'m>(&mut self, simulation: &Simulation<'p, 'm>, player: &'p Player) -> Vec<Owned<'p, Order>> {
+        self.policy.update(&mut self.policy_state, simulation.view_for(player))
+            .into_iter()
+            .map(|order| Owned(player, order))
+            .collect()
+    }
+
+    // Applies my orders together with enemy orders given by the playout policy
+    fn step<'m>(&mut self, simulation: &mut Simulation<'p, 'm>, orders: Vec<Order>) {
+        let mut orders = orders.into_iter().map(|order| Owned(self.player, order)).collect::<Vec<_>>();
+        for enemy in self.enemies.clone() {
+            orders.extend(self.policy_orders(simulation, enemy));
+        }
+        simulation.apply(orders.into_iter());
+    }
+
+    fn playout<'m>(&mut self, mut simulation: Simulation<'p, 'm>, cycles: u64) -> Simulation<'p, 'm> {
+        for _ in 0..cycles {
+            let orders = self.policy_orders(&simulation, self.player)
+                .into_iter()
+                .map(|Owned(_, order)| order)
+                .collect();
+            self.step(&mut simulation, orders);
+        }
+        simulation
+    }
+}
+
+impl<'p, 'm> Node<'p, 'm> {
+    fn new(simulation: Simulation<'p, 'm>, player: &'p Player) -> Node<'p, 'm> {
+        let units = simulation.view_for(player).all_legal_orders();
+        let local = units.iter().map(|(_, orders)| vec![Arm::default(); orders.len() + 1]).collect();
+        Node {
+            simulation,
+            units,
+            local,
+            children: Vec::new(),
+        }
+    }
+
+    fn orders(&self, choices: &[usize]) -> Vec<Order> {
+        self.units.iter()
+            .zip(choices)
+            .filter(|&(_, &choice)| choice > 0)
+            .map(|((_, orders), &choice)| orders[choice - 1])
+            .collect()
+    }
+
+    // Naive sampling: either explore new combination of orders built from each unit's local
+    // bandit or exploit the best combination tried so far
+    fn sample<P: AI>(&mut self, search: &mut Search<'p, P>, config: &NaiveMCTS<P>) -> usize {
+        if self.children.is_empty() || search.rng.next_f64() < config.epsilon_0 {
+            let choices = self.local.iter()
+                .map(|arms| {
+                    if search.rng.next_f64() < config.epsilon_local {
+                        search.rng.gen_range(0, arms.len())
+                    } else {
+                        (0..arms.len())
+                            .max_by(|&a, &b| arms[a].mean().partial_cmp(&arms[b].mean()).expect("NaN value"))
+                            .expect("no options")
+                    }
+                })
+                .collect::<Vec<_>>();
+
+            if let Some(index) = self.children.iter().position(|(existing, _, _)| *existing == choices) {
+                return index;
+            }
+
+            let mut simulation = self.simulation.clone();
+            search.step(&mut simulation, self.orders(&choices));
+            let node = Node::new(simulation, search.player);
+            self.children.push((choices, Arm::default(), node));
+            self.children.len() - 1
+        } else if search.rng.next_f64() < config.epsilon_global {
+            search.rng.gen_range(0, self.children.len())
+        } else {
+            self.best_child(|arm| arm.mean())
+        }
+    }
+
+    fn best_child<F: Fn(&Arm) -> f64>(&self, score: F) -> usize {
+        (0..self.children.len())
+            .max_by(|&a, &b| score(&self.children[a].1).partial_cmp(&score(&self.children[b].1)).expect("NaN value"))
+            .expect("no children")
+    }
+
+    // One iteration of search from this node returning reward of the playout
+    fn iterate<P: AI>(&mut self, search: &mut Search<'p, P>, config: &NaiveMCTS<P>, depth: usize) -> f64 {
+        if depth >= config.max_depth {
+            let simulation = search.playout(self.simulation.clone(), config.playout_cycles);
+            return config.evaluation.evaluate(&simulation.view_for(search.player));
+        }
+
+        let new_child = self.children.len();
+        let index = self.sample(search, config);
+
+        let reward = if index == new_child {
+            let simulation = search.playout(self.children[index].2.simulation.clone(), config.playout_cycles);
+            config.evaluation.evaluate(&simulation.view_for(search.player))
+        } else {
+            self.children[index].2.iterate(search, config, depth + 1)
+        };
+
+        let (ref choices, ref mut arm, _) = self.children[index];
+        arm.update(reward);
+        for (arms, &choice) in self.local.iter_mut().zip(choices) {
+            arms[choice].update(reward);
+        }
+        reward
+    }
+}
+
+// Monte Carlo tree search over combined orders of all my units (NaiveMCTS by Ontañón). Too many
+// combinations exist to try them all, so they are sampled treating each unit as separate
+// multi-armed bandit. Each tree level is one game cycle; enemy orders are given by the playout
+// policy and sampled once when a node is created. Playouts are run with playout policy AI for all
+// players (RandomBiasedAI by default or any scripted AI) and evaluated from my point of view.
+pub struct NaiveMCTS<P: AI = RandomBiasedAI> {
+    pub budget: Budget,
+    pub max_depth: usize,
+    pub playout_cycles: u64,
+    // Probability of exploring new combination of orders instead of exploiting known one
+    pub epsilon_0: f64,
+    // Probability of picking random option of a unit when exploring
+    pub epsilon_local: f64,
+    // Probability of picking random known combination when exploiting
+    pub epsilon_global: f64,
+    pub evaluation: Box<dyn Evaluation + Send>,
+    policy: Option<P>,
+    rng: XorShiftRng,
+}
+
+impl<P: AI> NaiveMCTS<P> {
+    pub fn with_playout(policy: P, seed: u64) -> NaiveMCTS<P> {
+        NaiveMCTS {
+            budget: Budget::default(),
+            max_depth: 10,
+            playout_cycles: 20,
+            epsilon_0: 0.4,
+            epsilon_local: 0.3,
+            epsilon_global: 0.0,
+            evaluation: Box::new(Material),
+            policy: Some(policy),
+            rng: seeded_rng(seed),
+        }
+    }
+}
+
+impl<P: AI> Default for NaiveMCTS<P> {
+    fn default() -> NaiveMCTS<P> {
+        NaiveMCTS::with_playout(P::default(), random_seed())
+    }
+}
+
+impl<P: AI> AI for NaiveMCTS<P> {
+    type PersistentState = EmptyPersistentState;
+    fn update<'p: 'gs, 's: 'gs, 't: 'gs, 'gs>(
+        &mut self,
+        _sate: &'s mut Self::PersistentState,
+        view: GameView<'p, 't, 'gs>,
+    ) -> Vec<Order> {
+        let started = Instant::now();
+
+        let mut search = Search {
+            player: view.player,
+            enemies: view.enemies(),
+            policy: self.policy.take().expect("search already running"),
+            policy_state: Default::default(),
+            rng: self.rng.clone(),
+        };
+
+        let mut root = Node::new(view.simulation(), view.player);
+        let mut iterations = 0;
+        while iterations == 0 || !self.budget.exhausted(iterations, started) {
+            root.iterate(&mut search, self, 0);
+            iterations += 1;
+        }
+
+        self.policy = Some(search.policy);
+        self.rng = search.rng;
+
+        if root.children.is_empty() {
+            return Vec::new();
+        }
+        let best = root.best_child(|arm| arm.visits as f64);
+        root.orders(&root.children[best].0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use game::{Coordinates, Object, Unit, Direction};
+    use game::test_support::TwoPlayers;
+
+    #[test]
+    fn naive_mcts_follows_evaluation() {
+        let players = TwoPlayers::default();
+        let (p1, p2) = (&players.p1, &players.p2);
+
+        let game = players.game(&[
+            (Coordinates(3, 0), Object::Unit(p1, Unit::Worker)),
+            (Coordinates(6, 6), Object::Unit(p2, Unit::Worker)),
+        ]);
+
+        // Worker is better off the closer it is to top left corner
+        fn to_corner(view: &GameView) -> f64 {
+            let location = view.my_units().next().unwrap().navigator.location;
+            -(location.coordinates.manhattan_distance(Coordinates(0, 0)) as f64)
+        }
+
+        let new_ai = || NaiveMCTS::<RandomBiasedAI> {
+            budget: Budget { iterations: Some(300), time: None },
+            max_depth: 3,
+            playout_cycles: 0,
+            evaluation: Box::new(to_corner),
+            ..NaiveMCTS::with_playout(RandomBiasedAI::with_seed(1), 2)
+        };
+
+        let worker = game.view_for(p1).my_units().next().unwrap().entity_id;
+        let orders = new_ai().update(&mut EmptyPersistentState, game.view_for(p1));
+        assert_eq!(orders, vec![Order::Move(worker, Direction::Left)]);
+
+        // Same seed gives the same result
+        assert_eq!(new_ai().update(&mut EmptyPersistentState, game.view_for(p1)), orders);
+    }
+}
@@ -0,0 +1,388 @@
+use std::collections::{HashMap, HashSet};
+use std::time::Instant;
+
+use game::{AI, AnytimeAI, EmptyPersistentState, Order, Object, Unit, EntityID, Entity, Location, Player, Owned};
+use game::evaluation::{Evaluation, Material};
+use game_view::{GameView, Simulation};
+use ai::budget::Budget;
+
+// Simple per unit behaviours portfolio search chooses from
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Script {
+    AttackClosest,
+    AttackWeakest,
+    Harvest,
+    Retreat,
+    Kite,
+}
+
+pub const PORTFOLIO: [Script; 5] = [
+    Script::AttackClosest,
+    Script::AttackWeakest,
+    Script::Harvest,
+    Script::Retreat,
+    Script::Kite,
+];
+
+// Legal order moving unit one step along the shortest path towards target
+fn approach(view: &GameView, unit: &Entity, target: Location) -> Option<Order> {
+    let from = view.navigator(unit.location);
+    let (path, _cost) = from.find_path_astar(&view.navigator(target))?;
+    let direction = from.direction_to(path.get(1)?)?;
+    let order = Order::Move(unit.id, direction);
+    if view.legal_orders(unit.id).contains(&order) { Some(order) } else { None }
+}
+
+// Legal order that gets unit furthest away from given location if that is further than it is now
+fn flee(view: &GameView, unit: &Entity, from: Location) -> Option<Order> {
+    let current = view.distance(unit.location, from);
+    view.legal_orders(unit.id)
+        .into_iter()
+        .filter_map(|order| match order {
+            Order::Move(_, direction) => {
+                unit.location.in_direction(direction).map(|to| (view.distance(to, from), order))
+            }
+            _ => None,
+        })
+        .filter(|&(distance, _)| distance > current)
+        .max_by_key(|&(distance, _)| distance)
+        .map(|(_, order)| order)
+}
+
+impl Script {
+    // Order for the unit this cycle; None to wait
+    pub fn order(&self, view: &GameView, entity_id: EntityID) -> Option<Order> {
+        let unit = view.entity(entity_id)?;
+        let (attack_range, is_worker) = match unit.object {
+            Object::Unit(_, ref kind) => (kind.attack_range(), *kind == Unit::Worker),
+            _ => return None,
+        };
+        let closest_enemy = view.closest_enemy(unit.location);
+
+        match *self {
+            Script::AttackClosest => approach(view, unit, closest_enemy?.location),
+            Script::AttackWeakest => {
+                let weakest = view.entities()
+                    .map(|(_, entity)| entity)
+                    .filter(|entity| view.is_enemy(entity))
+                    .filter_map(|entity| hit_points(entity).map(|hit_points| (hit_points, entity)))
+                    .min_by_key(|&(hit_points, entity)| (hit_points, view.distance(unit.location, entity.location)))?;
+                approach(view, unit, weakest.1.location)
+            }
+            Script::Harvest => {
+                if !is_worker {
+                    return None;
+                }
+                let metric = view.connectivity().metric();
+                let resource = view.closest_entity(unit.location, metric, |entity| {
+                    matches!(entity.object, Object::Resources(..))
+                })?;
+                approach(view, unit, resource.location)
+            }
+            Script::Retreat => {
+                let metric = view.connectivity().metric();
+                let base = view.closest_entity(unit.location, metric, |entity| {
+                    view.is_mine(entity) && matches!(entity.object, Object::Building(..))
+                });
+                match base {
+                    Some(base) => approach(view, unit, base.location),
+                    None => flee(view, unit, closest_enemy?.location),
+                }
+            }
+            Script::Kite => {
+                let enemy = closest_enemy?;
+                let distance = view.distance(unit.location, enemy.location);
+                if distance <= attack_range {
+                    flee(view, unit, enemy.location)
+                } else if distance > attack_range + 1 {
+                    approach(view, unit, enemy.location)
+                } else {
+                    None
+                }
+            }
+        }
+    }
+}
+
+fn hit_points(entity: &Entity) -> Option<u64> {
+    match entity.object {
+        Object::Unit(_, ref unit) => Some(unit.hit_points()),
+        Object::Building(_, ref building) => Some(building.hit_points()),
+        Object::Resources(..) => None,
+    }
+}
+
+// Script for every unit; units missing from assignment use the default script
+#[derive(Debug, Clone)]
+struct Assignment {
+    scripts: HashMap<EntityID, Script>,
+    default: Script,
+}
+
+impl Assignment {
+    fn script(&self, entity_id: EntityID) -> Script {
+        self.scripts.get(&entity_id).cloned().unwrap_or(self.default)
+    }
+
+    // Orders of player's units; units do not step onto location other unit is moving onto
+    fn orders(&self, view: &GameView) -> Vec<Order> {
+        let mut claimed = HashSet::new();
+        let mut units = view.my_units().map(|unit| unit.entity_id).collect::<Vec<_>>();
+        units.sort_by_key(|entity_id| entity_id.0);
+
+        units.into_iter()
+            .filter_map(|entity_id| self.script(entity_id).order(view, entity_id))
+            .filter(|&order| match order {
+                Order::Move(entity_id, direction) => {
+                    view.entity(entity_id)
+                        .and_then(|entity| entity.location.in_direction(direction))
+                        .map(|to| claimed.insert(to.index()))
+                        .unwrap_or(false)
+                }
+                _ => true,
+            })
+            .collect()
+    }
+}
+
+// Portfolio Greedy Search (Churchill and Buro): every unit is controlled by one of the scripts in
+// the portfolio. Starting with the single script that is best for all my units, the script of
+// each unit in turn is replaced by the one that gives the best evaluation after simulating
+// horizon cycles, while enemy units follow enemy_script. Improvement passes are repeated until
+// none changes anything or budget runs out; budget iterations limit number of passes.
+//
+// As anytime AI search carries over from one cycle to the next: when the game moves on, the
+// assignment found so far becomes the starting point of the search in the new cycle.
+pub struct PortfolioGreedySearch {
+    pub budget: Budget,
+    pub horizon: u64,
+    pub enemy_script: Script,
+    pub evaluation: Box<dyn Evaluation + Send>,
+    assignment: Assignment,
+    value: f64,
+    // Cycle the search is rooted at
+    cycle: Option<u64>,
+    units: Vec<EntityID>,
+    // Position within current improvement pass over units and scripts
+    next: usize,
+    improved: bool,
+    passes: usize,
+}
+
+impl Default for PortfolioGreedySearch {
+    fn default() -> PortfolioGreedySearch {
+        PortfolioGreedySearch {
+            budget: Budget::default(),
+            horizon: 10,
+            enemy_script: Script::AttackClosest,
+            evaluation: Box::new(Material),
+            assignment: Assignment {
+                scripts: HashMap::new(),
+                default: PORTFOLIO[0],
+            },
+            value: f64::NEG_INFINITY,
+            cycle: None,
+            units: Vec::new(),
+            next: 0,
+            improved: false,
+            passes: 0,
+        }
+    }
+}
+
+impl PortfolioGreedySearch {
+    // Script the unit was assigned in the last update
+    pub fn script_of(&self, entity_id: EntityID) -> Option<Script> {
+        self.assignment.scripts.get(&entity_id).cloned()
+    }
+
+    fn playout<'p, 'm>(
+        &self,
+        simulation: &Simulation<'p, 'm>,
+        player: &'p Player,
+        enemies: &[&'p Player],
+        mine: &Assignment,
+    ) -> f64 {
+        let theirs = Assignment {
+            scripts: HashMap::new(),
+            default: self.enemy_script,
+        };
+
+        let mut simulation = simulation.clone();
+        for _ in 0..self.horizon {
+            let mut orders = mine.orders(&simulation.view_for(player))
+                .into_iter()
+                .map(|order| Owned(player, order))
+                .collect::<Vec<_>>();
+            for &enemy in enemies {
+                let enemy_orders = theirs.orders(&simulation.view_for(enemy));
+                orders.extend(enemy_orders.into_iter().map(|order| Owned(enemy, order)));
+            }
+            simulation.apply(orders.into_iter());
+        }
+        self.evaluation.evaluate(&simulation.view_for(player))
+    }
+}
+
+impl PortfolioGreedySearch {
+    // Roots search at the cycle of the view, starting from the last assignment or one of given
+    // scripts for all units, whichever is best
+    fn root<'p>(
+        &mut self,
+        view: &GameView<'p, '_, '_>,
+        simulation: &Simulation<'p, '_>,
+        enemies: &[&'p Player],
+        scripts: &[Script],
+    ) {
+        self.units = view.my_units().map(|unit| unit.entity_id).collect();
+        self.units.sort_by_key(|entity_id| entity_id.0);
+
+        let mut previous = self.assignment.clone();
+        previous.scripts.retain(|entity_id, _| view.entity(*entity_id).is_some());
+        let value = self.playout(simulation, view.player, enemies, &previous);
+        let mut best = (previous, value);
+        for &script in scripts {
+            let assignment = Assignment { scripts: HashMap::new(), default: script };
+            let value = self.playout(simulation, view.player, enemies, &assignment);
+            if value > best.1 {
+                best = (assignment, value);
+            }
+        }
+
+        let (mut assignment, value) = best;
+        for &entity_id in &self.units {
+            let script = assignment.script(entity_id);
+            assignment.scripts.insert(entity_id, script);
+        }
+        self.assignment = assignment;
+        self.value = value;
+        self.cycle = Some(view.cycle());
+        self.next = 0;
+        self.improved = false;
+        self.passes = 0;
+    }
+
+    // Tries one script for one unit; returns false when the search is finished
+    fn step<'p>(
+        &mut self,
+        view: &GameView<'p, '_, '_>,
+        simulation: &Simulation<'p, '_>,
+        enemies: &[&'p Player],
+    ) -> bool {
+        let candidates = self.units.len() * PORTFOLIO.len();
+        if self.next >= candidates {
+            return false;
+        }
+
+        let entity_id = self.units[self.next / PORTFOLIO.len()];
+        let script = PORTFOLIO[self.next % PORTFOLIO.len()];
+        if self.assignment.script(entity_id) != script {
+            let mut candidate = self.assignment.clone();
+            candidate.scripts.insert(entity_id, script);
+            let value = self.playout(simulation, view.player, enemies, &candidate);
+            if value > self.value {
+                self.assignment = candidate;
+                self.value = value;
+                self.improved = true;
+            }
+        }
+
+        self.next += 1;
+        if self.next == candidates {
+            self.passes += 1;
+            // Another pass only if this one changed something
+            if self.improved {
+                self.next = 0;
+                self.improved = false;
+            }
+        }
+        self.next < candidates
+    }
+}
+
+impl AnytimeAI for PortfolioGreedySearch {
+    type PersistentState = EmptyPersistentState;
+
+    fn start(&mut self, _sate: &mut Self::PersistentState, view: &GameView) {
+        let simulation = view.simulation();
+        self.root(view, &simulation, &view.enemies(), &PORTFOLIO);
+    }
+
+    fn improve(&mut self, _sate: &mut Self::PersistentState, view: &GameView, deadline: Instant) {
+        let simulation = view.simulation();
+        let enemies = view.enemies();
+        // Game moved on; continue from the assignment found so far
+        if self.cycle != Some(view.cycle()) {
+            self.root(view, &simulation, &enemies, &[]);
+        }
+        while Instant::now() < deadline && self.step(view, &simulation, &enemies) {}
+    }
+
+    fn best(&mut self, _sate: &mut Self::PersistentState, view: &GameView) -> Vec<Order> {
+        self.assignment.orders(view)
+    }
+}
+
+impl AI for PortfolioGreedySearch {
+    type PersistentState = EmptyPersistentState;
+    fn update<'p: 'gs, 's: 'gs, 't: 'gs, 'gs>(
+        &mut self,
+        sate: &'s mut Self::PersistentState,
+        view: GameView<'p, 't, 'gs>,
+    ) -> Vec<Order> {
+        let started = Instant::now();
+        let simulation = view.simulation();
+        let enemies = view.enemies();
+        self.root(&view, &simulation, &enemies, &PORTFOLIO);
+        while !self.budget.exhausted(self.passes, started) && self.step(&view, &simulation, &enemies) {}
+        self.best(sate, &view)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use game::{Coordinates, Resource, Building, Direction};
+    use game::test_support::TwoPlayers;
+
+    #[test]
+    fn portfolio_picks_script_by_evaluation() {
+        let players = TwoPlayers::default();
+        let (p1, p2) = (&players.p1, &players.p2);
+
+        let game = players.game(&[
+            (Coordinates(0, 0), Object::Resources(Resource(10))),
+            (Coordinates(3, 0), Object::Unit(p1, Unit::Worker)),
+            (Coordinates(3, 1), Object::Unit(p1, Unit::Light)),
+            (Coordinates(6, 6), Object::Building(p2, Building::Barracks)),
+        ]);
+
+        // Worker should harvest and Light attack
+        fn objectives(view: &GameView) -> f64 {
+            let mut value = 0.0;
+            for unit in view.my_units() {
+                let target = match *unit.unit {
+                    Unit::Worker => Coordinates(0, 0),
+                    _ => Coordinates(6, 6),
+                };
+                value -= unit.navigator.location.coordinates.manhattan_distance(target) as f64;
+            }
+            value
+        }
+
+        let mut ai = PortfolioGreedySearch {
+            budget: Budget { iterations: Some(3), time: None },
+            horizon: 4,
+            evaluation: Box::new(objectives),
+            ..Default::default()
+        };
+        let orders = ai.update(&mut EmptyPersistentState, game.view_for(p1));
+
+        let view = game.view_for(p1);
+        let worker = view.my_units().find(|unit| *unit.unit == Unit::Worker).unwrap().entity_id;
+        let light = view.my_units().find(|unit| *unit.unit == Unit::Light).unwrap().entity_id;
+        assert_eq!(ai.script_of(worker), Some(Script::Harvest));
+        assert!(ai.script_of(light) != Some(Script::Harvest) && ai.script_of(light) != Some(Script::Retreat));
+        assert!(orders.contains(&Order::Move(worker, Direction::Left)));
+    }
+}
@@ -0,0 +1,180 @@
+use rand::{self, Rng, SeedableRng, XorShiftRng};
+
+use game::{AI, EmptyPersistentState, Order, Unit, Object};
+use game_view::GameView;
+
+// Generator seeded so that games can be replayed
+pub fn seeded_rng(seed: u64) -> XorShiftRng {
+    // XorShift must not be seeded with all zeros
+    XorShiftRng::from_seed([seed as u32, (seed >> 32) as u32, 0x9e37_79b9, 0x7f4a_7c15])
+}
+
+// Seed different for every call so that AIs created with default() do not play the same
+pub fn random_seed() -> u64 {
+    rand::thread_rng().gen()
+}
+
+// Gives every unit that can act one of its legal orders picked uniformly
+pub struct RandomAI {
+    rng: XorShiftRng,
+}
+
+impl RandomAI {
+    pub fn with_seed(seed: u64) -> RandomAI {
+        RandomAI {
+            rng: seeded_rng(seed),
+        }
+    }
+}
+
+impl Default for RandomAI {
+    fn default() -> RandomAI {
+        RandomAI::with_seed(random_seed())
+    }
+}
+
+impl AI for RandomAI {
+    type PersistentState = EmptyPersistentState;
+    fn update<'p: 'gs, 's: 'gs, 't: 'gs, 'gs>(
+        &mut self,
+        _sate: &'s mut Self::PersistentState,
+        view: GameView<'p, 't, 'gs>,
+    ) -> Vec<Order> {
+        let rng = &mut self.rng;
+        view.all_legal_orders()
+            .into_iter()
+            .filter_map(|(_entity_id, orders)| rng.choose(&orders).cloned())
+            .collect()
+    }
+}
+
+// Weight of orders the biased AI prefers relative to other orders
+const BIASED_WEIGHT: u32 = 5;
+
+// Like RandomAI but harvest and return orders, and moves that bring unit closer to the nearest
+// enemy or worker closer to the nearest resource, are picked BIASED_WEIGHT times more often. There
+// are no attack orders yet so approaching the enemy stands in for them.
+pub struct RandomBiasedAI {
+    rng: XorShiftRng,
+}
+
+impl RandomBiasedAI {
+    pub fn with_seed(seed: u64) -> RandomBiasedAI {
+        RandomBiasedAI {
+            rng: seeded_rng(seed),
+        }
+    }
+}
+
+impl Default for RandomBiasedAI {
+    fn default() -> RandomBiasedAI {
+        RandomBiasedAI::with_seed(random_seed())
+    }
+}
+
+impl AI for RandomBiasedAI {
+    type PersistentState = EmptyPersistentState;
+    fn update<'p: 'gs, 's: 'gs, 't: 'gs, 'gs>(
+        &mut self,
+        _sate: &'s mut Self::PersistentState,
+        view: GameView<'p, 't, 'gs>,
+    ) -> Vec<Order> {
+        let metric = view.connectivity().metric();
+        let mut desires = Vec::new();
+
+        for (entity_id, orders) in view.all_legal_orders() {
+            let entity = view.entity(entity_id).expect("unit with orders does not exist");
+            let location = entity.location;
+
+            let mut targets = Vec::new();
+            if let Some(enemy) = view.closest_enemy(location) {
+                targets.push(enemy.location);
+            }
+            if let Object::Unit(_, Unit::Worker) = entity.object {
+                let resource = view.closest_entity(location, metric, |entity| matches!(entity.object, Object::Resources(..)));
+                if let Some(resource) = resource {
+                    targets.push(resource.location);
+                }
+            }
+
+            let weights = orders.iter()
+                .map(|order| match *order {
+                    Order::Move(_, direction) => {
+                        let approaches = location.in_direction(direction)
+                            .map(|to| {
+                                targets.iter().any(|target| {
+                                    to.coordinates.distance(target.coordinates, metric) <
+                                        location.coordinates.distance(target.coordinates, metric)
+                                })
+                            })
+                            .unwrap_or(false);
+                        if approaches { BIASED_WEIGHT } else { 1 }
+                    }
+                    Order::Harvest(..) | Order::Return(..) => BIASED_WEIGHT,
+                    Order::Train(..) | Order::Build(..) => 1,
+                })
+                .collect::<Vec<_>>();
+
+            let mut pick = self.rng.gen_range(0, weights.iter().sum::<u32>());
+            for (order, weight) in orders.iter().zip(weights) {
+                if pick < weight {
+                    desires.push(*order);
+                    break;
+                }
+                pick -= weight;
+            }
+        }
+
+        desires
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use game::{Coordinates, Resource};
+    use game::test_support::TwoPlayers;
+
+    #[test]
+    fn random_ais_are_reproducible() {
+        let players = TwoPlayers::default();
+        let (p1, p2) = (&players.p1, &players.p2);
+
+        let game = players.game(&[
+            (Coordinates(0, 0), Object::Resources(Resource(10))),
+            (Coordinates(2, 2), Object::Unit(p1, Unit::Worker)),
+            (Coordinates(3, 2), Object::Unit(p1, Unit::Light)),
+            (Coordinates(6, 6), Object::Unit(p2, Unit::Heavy)),
+        ]);
+
+        let play = |seed| {
+            let mut random = RandomAI::with_seed(seed);
+            let mut biased = RandomBiasedAI::with_seed(seed);
+            let mut state = EmptyPersistentState;
+            (0..20)
+                .map(|_| {
+                    let orders = random.update(&mut state, game.view_for(p1));
+                    let biased_orders = biased.update(&mut state, game.view_for(p1));
+                    (orders, biased_orders)
+                })
+                .collect::<Vec<_>>()
+        };
+
+        let orders = play(42);
+        assert_eq!(orders, play(42));
+        assert!(orders != play(7));
+
+        // AIs created with default() are seeded differently
+        assert!(RandomAI::default().rng.next_u64() != RandomAI::default().rng.next_u64());
+
+        // Every unit gets a legal order
+        let legal = game.view_for(p1).all_legal_orders();
+        for (random, biased) in &orders {
+            assert_eq!(random.len(), 2);
+            assert_eq!(biased.len(), 2);
+            for order in random.iter().chain(biased) {
+                assert!(legal.iter().any(|(_, orders)| orders.contains(order)));
+            }
+        }
+    }
+}
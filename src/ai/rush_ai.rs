@@ -0,0 +1,355 @@
+use std::collections::HashSet;
+
+use game::{AI, EmptyPersistentState, Order, Object, Unit, Building, EntityID, Location};
+use game_view::{GameView, PathCache};
+
+// Scripted rushes after the hard-coded microRTS strategies: fixed number of workers harvest, base
+// trains workers and barracks, for rushes that need one, trains army units whenever there are
+// resources for them. Army units walk to the closest enemy, remaining units only fight enemies
+// that come close. There are no attack orders yet, so fighting units stop next to their target.
+
+// Distance at which units that are not part of the army engage enemies
+const DEFENCE_RADIUS: usize = 3;
+
+#[derive(Debug, Clone)]
+struct Rush {
+    army: Unit,
+    harvesters: usize,
+    paths: PathCache,
+}
+
+// Orders given so far this cycle
+struct Plan<'m> {
+    orders: Vec<Order>,
+    ordered: HashSet<EntityID>,
+    // Locations units move onto or new entities are placed on
+    claimed: HashSet<Location<'m>>,
+    // Resources left after paying for the orders
+    budget: u64,
+}
+
+impl<'m> Plan<'m> {
+    fn give(&mut self, order: Order, target: Location<'m>, cost: u64) -> bool {
+        let takes_target = !matches!(order, Order::Harvest(..) | Order::Return(..));
+        if cost > self.budget || self.ordered.contains(&order.entity_id()) || (takes_target && self.claimed.contains(&target)) {
+            return false;
+        }
+
+        if takes_target {
+            self.claimed.insert(target);
+        }
+        self.ordered.insert(order.entity_id());
+        self.budget -= cost;
+        self.orders.push(order);
+        true
+    }
+
+    // Gives first legal order of the entity that is accepted and can be given
+    fn give_any<'p, 's, F>(&mut self, view: &GameView<'p, 'm, 's>, entity_id: EntityID, cost: u64, accept: F) -> bool
+    where
+        F: Fn(Order) -> bool,
+    {
+        let location = match view.entity(entity_id) {
+            Some(entity) => entity.location,
+            None => return false,
+        };
+
+        view.legal_orders(entity_id)
+            .into_iter()
+            .filter(|&order| accept(order))
+            .any(|order| {
+                location.in_direction(order.direction())
+                    .map(|target| self.give(order, target, cost))
+                    .unwrap_or(false)
+            })
+    }
+}
+
+impl Rush {
+    fn new(army: Unit, harvesters: usize) -> Rush {
+        Rush {
+            army,
+            harvesters,
+            paths: PathCache::new(),
+        }
+    }
+
+    fn orders(&mut self, view: &GameView) -> Vec<Order> {
+        let mut plan = Plan {
+            orders: Vec::new(),
+            ordered: HashSet::new(),
+            claimed: HashSet::new(),
+            budget: view.my_resources(),
+        };
+
+        let mut mine = view.entities()
+            .map(|(_, entity)| entity)
+            .filter(|entity| view.is_mine(entity))
+            .collect::<Vec<_>>();
+        mine.sort_by_key(|entity| entity.id.0);
+
+        let workers = mine.iter()
+            .filter(|entity| matches!(entity.object, Object::Unit(_, Unit::Worker)))
+            .map(|entity| entity.id)
+            .collect::<Vec<_>>();
+        // Workers with lowest IDs keep harvesting
+        let harvesters = workers.iter().take(self.harvesters).cloned().collect::<HashSet<_>>();
+
+        let bases = mine.iter()
+            .filter(|entity| matches!(entity.object, Object::Building(_, Building::Base(_))))
+            .map(|entity| view.navigator(entity.location))
+            .collect::<Vec<_>>();
+        let resources = view.resources()
+            .map(|resource| resource.navigator)
+            .collect::<Vec<_>>();
+
+        // Barracks come first as there is no army without them; built by a worker that is not
+        // harvesting if there is one
+        let has_barracks = mine.iter().any(|entity| matches!(entity.object, Object::Building(_, Building::Barracks)));
+        if self.army != Unit::Worker && !has_barracks {
+            let idle = workers.iter()
+                .cloned()
+                .filter(|&entity_id| view.busy_for(entity_id) == 0 && view.carried(entity_id) == 0)
+                .collect::<Vec<_>>();
+            let builder = idle.iter().find(|entity_id| !harvesters.contains(entity_id)).or_else(|| idle.last());
+            if let Some(&builder) = builder {
+                plan.give_any(view, builder, Building::Barracks.cost(), |order| matches!(order, Order::Build(..)));
+            }
+        }
+
+        // Army from barracks; workers from bases until there are enough harvesters unless workers
+        // are the army
+        for entity in &mine {
+            let unit = match entity.object {
+                Object::Building(_, Building::Barracks) => self.army,
+                Object::Building(_, Building::Base(_)) if self.army == Unit::Worker || workers.len() < self.harvesters => {
+                    Unit::Worker
+                }
+                _ => continue,
+            };
+            plan.give_any(view, entity.id, unit.cost(), |order| matches!(order, Order::Train(_, _, trained) if trained == unit));
+        }
+
+        for entity in &mine {
+            let unit = match entity.object {
+                Object::Unit(_, unit) => unit,
+                _ => continue,
+            };
+            if plan.ordered.contains(&entity.id) || view.busy_for(entity.id) > 0 {
+                continue;
+            }
+
+            let navigator = view.navigator(entity.location);
+            let path = if harvesters.contains(&entity.id) {
+                // Harvesters go back and forth between resources and bases
+                let (goals, action): (_, fn(Order) -> bool) = if view.carried(entity.id) > 0 {
+                    (&bases, |order| matches!(order, Order::Return(..)))
+                } else {
+                    (&resources, |order| matches!(order, Order::Harvest(..)))
+                };
+                if plan.give_any(view, entity.id, 0, action) {
+                    continue;
+                }
+                self.paths.path_to_nearest(entity.id, &navigator, goals)
+                    .map(|(path, _goal)| path)
+            } else {
+                let location = entity.location;
+                view.closest_enemy(location)
+                    .filter(|enemy| unit == self.army || view.distance(location, enemy.location) <= DEFENCE_RADIUS)
+                    .map(|enemy| view.navigator(enemy.location))
+                    .and_then(|enemy| self.paths.path_to(entity.id, &navigator, &enemy))
+            };
+
+            if let Some(next) = path.as_ref().and_then(|path| path.get(1)) {
+                if let Some(direction) = navigator.direction_to(next) {
+                    plan.give(Order::Move(entity.id, direction), next.location, 0);
+                }
+            }
+        }
+
+        plan.orders
+    }
+}
+
+// Base keeps training workers; one harvests and all other workers attack
+#[derive(Debug, Clone)]
+pub struct WorkerRush {
+    rush: Rush,
+}
+
+impl WorkerRush {
+    pub fn with_harvesters(harvesters: usize) -> WorkerRush {
+        WorkerRush {
+            rush: Rush::new(Unit::Worker, harvesters),
+        }
+    }
+}
+
+impl Default for WorkerRush {
+    fn default() -> WorkerRush {
+        WorkerRush::with_harvesters(1)
+    }
+}
+
+impl AI for WorkerRush {
+    type PersistentState = EmptyPersistentState;
+    fn update<'p: 'gs, 's: 'gs, 't: 'gs, 'gs>(
+        &mut self,
+        _sate: &'s mut Self::PersistentState,
+        view: GameView<'p, 't, 'gs>,
+    ) -> Vec<Order> {
+        self.rush.orders(&view)
+    }
+}
+
+// One worker harvests and builds barracks that keep training Light units to attack
+#[derive(Debug, Clone)]
+pub struct LightRush {
+    rush: Rush,
+}
+
+impl LightRush {
+    pub fn with_harvesters(harvesters: usize) -> LightRush {
+        LightRush {
+            rush: Rush::new(Unit::Light, harvesters),
+        }
+    }
+}
+
+impl Default for LightRush {
+    fn default() -> LightRush {
+        LightRush::with_harvesters(1)
+    }
+}
+
+impl AI for LightRush {
+    type PersistentState = EmptyPersistentState;
+    fn update<'p: 'gs, 's: 'gs, 't: 'gs, 'gs>(
+        &mut self,
+        _sate: &'s mut Self::PersistentState,
+        view: GameView<'p, 't, 'gs>,
+    ) -> Vec<Order> {
+        self.rush.orders(&view)
+    }
+}
+
+// Like LightRush with Heavy units
+#[derive(Debug, Clone)]
+pub struct HeavyRush {
+    rush: Rush,
+}
+
+impl HeavyRush {
+    pub fn with_harvesters(harvesters: usize) -> HeavyRush {
+        HeavyRush {
+            rush: Rush::new(Unit::Heavy, harvesters),
+        }
+    }
+}
+
+impl Default for HeavyRush {
+    fn default() -> HeavyRush {
+        HeavyRush::with_harvesters(1)
+    }
+}
+
+impl AI for HeavyRush {
+    type PersistentState = EmptyPersistentState;
+    fn update<'p: 'gs, 's: 'gs, 't: 'gs, 'gs>(
+        &mut self,
+        _sate: &'s mut Self::PersistentState,
+        view: GameView<'p, 't, 'gs>,
+    ) -> Vec<Order> {
+        self.rush.orders(&view)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use game::{Coordinates, Resource, Owned};
+    use game::test_support::TwoPlayers;
+
+    // Plays the rush for given number of cycles against enemy Heavy standing at (6, 6); all
+    // orders have to be legal. Returns my units with their distance to the enemy, resources left
+    // in the patch and whether I have barracks.
+    fn play<A>(mut ai: A, cycles: u64, units: &[(Coordinates, Unit)], base: u64) -> (Vec<(Unit, usize)>, u64, bool)
+    where
+        A: AI<PersistentState = EmptyPersistentState>,
+    {
+        let players = TwoPlayers::default();
+        let (p1, p2) = (&players.p1, &players.p2);
+
+        let mut placements = vec![
+            (Coordinates(0, 0), Object::Resources(Resource(20))),
+            (Coordinates(0, 2), Object::Building(p1, Building::Base(Resource(base)))),
+            (Coordinates(6, 6), Object::Unit(p2, Unit::Heavy)),
+        ];
+        placements.extend(units.iter().map(|&(coordinates, unit)| (coordinates, Object::Unit(p1, unit))));
+        let mut game = players.game(&placements);
+
+        let mut state = EmptyPersistentState;
+        for _ in 0..cycles {
+            let orders = ai.update(&mut state, game.view_for(p1));
+            let violations = game.apply(orders.into_iter().map(|order| Owned(p1, order)));
+            assert!(violations.is_empty(), "{:?}", violations);
+        }
+
+        let view = game.view_for(p1);
+        let mut units = view.my_units()
+            .map(|unit| (*unit.unit, unit.navigator.location.coordinates.manhattan_distance(Coordinates(6, 6))))
+            .collect::<Vec<_>>();
+        units.sort_by_key(|&(_, distance)| distance);
+        let left = view.resources().map(|resource| resource.resource.0).sum();
+        let has_barracks = view.entities().any(|(_, entity)| matches!(entity.object, Object::Building(_, Building::Barracks)));
+        (units, left, has_barracks)
+    }
+
+    #[test]
+    fn worker_rush_harvests_trains_and_attacks() {
+        let workers = [(Coordinates(2, 2), Unit::Worker), (Coordinates(2, 3), Unit::Worker)];
+        let (units, left, has_barracks) = play(WorkerRush::default(), 30, &workers, 0);
+
+        assert!(left < 20);
+        assert!(!has_barracks);
+        // Harvester stays at home, trained workers join the attack
+        assert!(units.len() > 3);
+        assert!(units.iter().all(|&(unit, _)| unit == Unit::Worker));
+        assert!(units.iter().filter(|&&(_, distance)| distance == 1).count() >= 2);
+        assert!(units.iter().filter(|&&(_, distance)| distance > 6).count() >= 1);
+    }
+
+    #[test]
+    fn light_and_heavy_rushes_build_barracks() {
+        let worker = [(Coordinates(1, 1), Unit::Worker)];
+
+        let (units, left, has_barracks) = play(LightRush::default(), 60, &worker, 6);
+        assert!(left < 20);
+        assert!(has_barracks);
+        assert_eq!(units.iter().filter(|&&(unit, _)| unit == Unit::Worker).count(), 1);
+        assert!(units.iter().any(|&(unit, distance)| unit == Unit::Light && distance == 1));
+
+        let (units, _, has_barracks) = play(HeavyRush::default(), 60, &worker, 6);
+        assert!(has_barracks);
+        assert!(units.iter().all(|&(unit, _)| unit != Unit::Light));
+        assert!(units.iter().any(|&(unit, distance)| unit == Unit::Heavy && distance == 1));
+    }
+
+    #[test]
+    fn rush_units_only_defend() {
+        let players = TwoPlayers::default();
+        let (p1, p2) = (&players.p1, &players.p2);
+
+        let game = players.game(&[
+            (Coordinates(2, 2), Object::Unit(p1, Unit::Light)),
+            (Coordinates(6, 6), Object::Unit(p2, Unit::Heavy)),
+        ]);
+
+        // Light unit is not part of the worker army and enemy is too far to defend against
+        let mut ai = WorkerRush::default();
+        let mut state = EmptyPersistentState;
+        for _ in 0..12 {
+            assert!(ai.update(&mut state, game.view_for(p1)).is_empty());
+        }
+    }
+}
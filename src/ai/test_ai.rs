@@ -1,8 +1,10 @@
 use game::{AI, EmptyPersistentState, Order, Unit};
-use game_view::GameView;
+use game_view::{GameView, PathCache};
 
 #[derive(Default)]
-pub struct TestAI;
+pub struct TestAI {
+    paths: PathCache,
+}
 
 impl AI for TestAI {
     type PersistentState = EmptyPersistentState;
@@ -13,23 +15,19 @@ impl AI for TestAI {
     ) -> Vec<Order> {
         let mut desires = Vec::new();
 
-        //TODO: remember path calculation
-        for unit in view.my_units() {
-            match unit.unit {
-                &Unit::Worker => {
-                    let mut resources_paths = view.resources()
-                        .filter_map(|resource| unit.navigator.find_path_dijkstra(&resource.navigator))
-                        .collect::<Vec<_>>();
-
-                    resources_paths.sort_by_key(|&(_, cost)| cost);
+        let resources = view.resources()
+            .map(|resource| resource.navigator)
+            .collect::<Vec<_>>();
 
-                    if let Some(&(ref path, _cost)) = resources_paths.first() {
-                        path.iter().skip(1).next()
-                            .and_then(|next_navigator| unit.navigator.direction_to(next_navigator))
-                            .map(|direction| desires.push(Order::Move(unit.entity_id, direction)));
+        for unit in view.my_units() {
+            if let &Unit::Worker = unit.unit {
+                if let Some((path, _resource)) = self.paths.path_to_nearest(unit.entity_id, &unit.navigator, &resources) {
+                    if let Some(direction) = path.get(1)
+                        .and_then(|next_navigator| unit.navigator.direction_to(next_navigator))
+                    {
+                        desires.push(Order::Move(unit.entity_id, direction));
                     }
                 }
-                _ => (),
             }
         }
 
@@ -3,15 +3,56 @@ use std::ops::RangeFrom;
 use std::collections::hash_map::Iter as HashMapIter;
 
 use game::player::Player;
-use game::terrain::Location;
+use game::terrain::{Location, AreaIter};
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum Unit {
     Worker,
     Light,
     Heavy,
 }
 
+// Unit and building statistics follow original microRTS unit type table; times are in cycles
+// of a move onto empty tile, which is a tenth of the original times
+impl Unit {
+    // Resources needed to produce the unit
+    pub fn cost(&self) -> u64 {
+        match *self {
+            Unit::Worker => 1,
+            Unit::Light | Unit::Heavy => 2,
+        }
+    }
+
+    // Cycles it takes to train the unit
+    pub fn production_time(&self) -> u64 {
+        match *self {
+            Unit::Worker => 5,
+            Unit::Light => 8,
+            Unit::Heavy => 12,
+        }
+    }
+
+    pub fn hit_points(&self) -> u64 {
+        match *self {
+            Unit::Worker => 1,
+            Unit::Light | Unit::Heavy => 4,
+        }
+    }
+
+    pub fn damage(&self) -> u64 {
+        match *self {
+            Unit::Worker => 1,
+            Unit::Light => 2,
+            Unit::Heavy => 4,
+        }
+    }
+
+    // Distance at which the unit can hit
+    pub fn attack_range(&self) -> usize {
+        1
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct Resource(pub u64);
 
@@ -21,6 +62,38 @@ pub enum Building {
     Barracks,
 }
 
+impl Building {
+    pub fn cost(&self) -> u64 {
+        match *self {
+            Building::Base(_) => 10,
+            Building::Barracks => 5,
+        }
+    }
+
+    // Cycles it takes to build the building
+    pub fn production_time(&self) -> u64 {
+        match *self {
+            Building::Base(_) => 25,
+            Building::Barracks => 20,
+        }
+    }
+
+    pub fn hit_points(&self) -> u64 {
+        match *self {
+            Building::Base(_) => 10,
+            Building::Barracks => 4,
+        }
+    }
+
+    // Base trains workers and barracks fighting units
+    pub fn can_train(&self, unit: Unit) -> bool {
+        matches!(
+            (self, unit),
+            (&Building::Base(_), Unit::Worker) | (&Building::Barracks, Unit::Light) | (&Building::Barracks, Unit::Heavy)
+        )
+    }
+}
+
 #[derive(Debug, Clone)]
 pub enum Object<'p> {
     Unit(&'p Player, Unit),
@@ -28,6 +101,15 @@ pub enum Object<'p> {
     Resources(Resource),
 }
 
+impl<'p> Object<'p> {
+    pub fn owner(&self) -> Option<&'p Player> {
+        match *self {
+            Object::Unit(owner, _) | Object::Building(owner, _) => Some(owner),
+            Object::Resources(_) => None,
+        }
+    }
+}
+
 // Using Copy object to reference entities to allow them to be modified, removed
 // etc. while AI are holding this
 #[derive(PartialEq, Eq, Hash, Debug, Clone, Copy)]
@@ -40,24 +122,26 @@ pub struct Entity<'t, 'p> {
     pub object: Object<'p>,
 }
 
-type LocationIndex<'t> = HashMap<Location<'t>, EntityID>;
+// Keyed by Location::index so it does not depend on Terrain instance
+type LocationIndex = HashMap<usize, EntityID>;
 
 #[derive(Debug, Clone)]
 pub struct Entities<'p, 't> {
     entities: HashMap<EntityID, Entity<'t, 'p>>,
     entity_id_seq: RangeFrom<usize>,
-    location_index: LocationIndex<'t>,
+    location_index: LocationIndex,
 }
 
 #[derive(Debug)]
 pub enum EntitiesError<'t> {
     LocationNotWalkable(Location<'t>),
+    LocationNotBuildable(Location<'t>),
     LocationAlreadyOccupied(Location<'t>, EntityID),
 }
 
 pub struct EntityMutator<'p: 'e, 't: 'e, 'e> {
     pub entity: &'e mut Entity<'t, 'p>,
-    location_index: &'e mut LocationIndex<'t>,
+    location_index: &'e mut LocationIndex,
 }
 
 impl<'p: 'e, 't: 'e, 'e> EntityMutator<'p, 't, 'e> {
@@ -67,17 +151,17 @@ impl<'p: 'e, 't: 'e, 'e> EntityMutator<'p, 't, 'e> {
             return Err(EntitiesError::LocationNotWalkable(location));
         }
 
-        if let Some(entity_id) = self.location_index.get(&location) {
+        if let Some(entity_id) = self.location_index.get(&location.index()) {
             return Err(EntitiesError::LocationAlreadyOccupied(location, *entity_id));
         }
 
         let entity_location = &mut self.entity.location;
 
         // Update indexes first
-        self.location_index.remove(&entity_location).expect(
+        self.location_index.remove(&entity_location.index()).expect(
             "bad location_index",
         );
-        self.location_index.insert(location, self.entity.id);
+        self.location_index.insert(location.index(), self.entity.id);
 
         // Update entity
         *entity_location = location;
@@ -86,6 +170,12 @@ impl<'p: 'e, 't: 'e, 'e> EntityMutator<'p, 't, 'e> {
     }
 }
 
+impl<'p, 't> Default for Entities<'p, 't> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl<'p, 't> Entities<'p, 't> {
     pub fn new() -> Entities<'p, 't> {
         Entities {
@@ -104,26 +194,65 @@ impl<'p, 't> Entities<'p, 't> {
             return Err(EntitiesError::LocationNotWalkable(location));
         }
 
-        if let Some(entity_id) = self.location_index.get(&location) {
+        match object {
+            Object::Unit(..) => (),
+            Object::Building(..) | Object::Resources(..) => if !location.buildable() {
+                return Err(EntitiesError::LocationNotBuildable(location));
+            },
+        }
+
+        if let Some(entity_id) = self.location_index.get(&location.index()) {
             return Err(EntitiesError::LocationAlreadyOccupied(location, *entity_id));
         }
 
         let entity_id = EntityID(self.entity_id_seq.next().expect("out of IDs"));
         let entity = Entity {
             id: entity_id,
-            location: location,
-            object: object,
+            location,
+            object,
         };
 
         if self.entities.insert(entity_id, entity).is_some() {
             panic!("duplicate ID");
         }
 
-        self.location_index.insert(location, entity_id);
+        self.location_index.insert(location.index(), entity_id);
 
         Ok(entity_id)
     }
 
+    // Puts entity back under the ID it had in another copy of the game; location is not checked
+    pub fn restore(&mut self, entity_id: EntityID, location: Location<'t>, object: Object<'p>) {
+        let entity = Entity {
+            id: entity_id,
+            location,
+            object,
+        };
+        if self.entities.insert(entity_id, entity).is_some() {
+            panic!("duplicate ID");
+        }
+        self.location_index.insert(location.index(), entity_id);
+        self.skip_ids(entity_id.0 + 1);
+    }
+
+    // ID the next placed entity gets
+    pub fn next_id(&self) -> usize {
+        self.entity_id_seq.start
+    }
+
+    // Makes IDs of entities placed from now on start at next_id at the earliest
+    pub fn skip_ids(&mut self, next_id: usize) {
+        if next_id > self.entity_id_seq.start {
+            self.entity_id_seq = next_id..;
+        }
+    }
+
+    pub fn remove(&mut self, entity_id: EntityID) -> Option<Entity<'t, 'p>> {
+        let entity = self.entities.remove(&entity_id)?;
+        self.location_index.remove(&entity.location.index()).expect("bad location_index");
+        Some(entity)
+    }
+
     pub fn get<'e>(&'e self, entity_id: EntityID) -> Option<&'e Entity<'t, 'p>> {
         self.entities.get(&entity_id)
     }
@@ -135,22 +264,56 @@ impl<'p, 't> Entities<'p, 't> {
         entities.get_mut(&entity_id).map(move |e| {
             EntityMutator {
                 entity: e,
-                location_index: location_index,
+                location_index,
             }
         })
     }
 
     pub fn get_by_location<'e>(&'e self, location: Location<'t>) -> Option<&'e Entity<'t, 'p>> {
-        self.location_index.get(&location).and_then(|entity_id| {
+        self.location_index.get(&location.index()).and_then(|entity_id| {
             self.get(*entity_id)
         })
     }
 
+    // Entities placed on locations of given area looked up by location index
+    pub fn in_area<'e>(&'e self, area: AreaIter<'t>) -> InAreaIter<'p, 't, 'e> {
+        InAreaIter {
+            entities: self,
+            area,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.entities.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entities.is_empty()
+    }
+
     pub fn iter<'e>(&'e self) -> Iter<'p, 't, 'e> {
         Iter { iter: self.entities.iter() }
     }
 }
 
+pub struct InAreaIter<'p: 'e, 't: 'e, 'e> {
+    entities: &'e Entities<'p, 't>,
+    area: AreaIter<'t>,
+}
+
+impl<'p: 'e, 't: 'e, 'e> Iterator for InAreaIter<'p, 't, 'e> {
+    type Item = &'e Entity<'t, 'p>;
+
+    fn next(&mut self) -> Option<&'e Entity<'t, 'p>> {
+        for location in self.area.by_ref() {
+            if let Some(entity) = self.entities.get_by_location(location) {
+                return Some(entity);
+            }
+        }
+        None
+    }
+}
+
 impl<'p: 'e, 't: 'e, 'e> IntoIterator for &'e Entities<'p, 't> {
     type IntoIter = Iter<'p, 't, 'e>;
     type Item = (EntityID, &'e Entity<'t, 'p>);
@@ -0,0 +1,124 @@
+use game::entity::{Object, Building, Resource};
+use game_view::GameView;
+
+// Scores game state for the player of the view: positive when the player is ahead, negative
+// when enemies are. Evaluations in this module stay within range -1 to 1; other implementations
+// may use any scale as searches only compare values.
+pub trait Evaluation {
+    fn evaluate(&self, view: &GameView) -> f64;
+}
+
+impl<F> Evaluation for F
+where
+    F: Fn(&GameView) -> f64,
+{
+    fn evaluate(&self, view: &GameView) -> f64 {
+        self(view)
+    }
+}
+
+// Difference of my and enemy scores relative to their sum
+fn balance(mine: f64, enemy: f64) -> f64 {
+    if mine + enemy == 0.0 {
+        0.0
+    } else {
+        (mine - enemy) / (mine + enemy)
+    }
+}
+
+// Weight of a resource stored in a base
+const RESOURCE_WEIGHT: f64 = 20.0;
+// Weight of resources spent on a unit or building
+const COST_WEIGHT: f64 = 40.0;
+
+// Resources and the cost of units and buildings weighted by square root of their remaining hit
+// point fraction (as microRTS SimpleSqrtEvaluationFunction3). Entities do not take damage yet so
+// they are always counted at full hit points.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct Material;
+
+impl Evaluation for Material {
+    fn evaluate(&self, view: &GameView) -> f64 {
+        let mut mine = 0.0;
+        let mut enemy = 0.0;
+
+        for (_entity_id, entity) in view.entities() {
+            let value = match entity.object {
+                Object::Unit(_, ref unit) => unit.cost() as f64 * COST_WEIGHT,
+                Object::Building(_, ref building) => {
+                    let stored = match *building {
+                        Building::Base(Resource(amount)) => amount,
+                        Building::Barracks => 0,
+                    };
+                    building.cost() as f64 * COST_WEIGHT + stored as f64 * RESOURCE_WEIGHT
+                }
+                Object::Resources(..) => continue,
+            };
+
+            if view.is_mine(entity) {
+                mine += value;
+            } else {
+                enemy += value;
+            }
+        }
+
+        balance(mine, enemy)
+    }
+}
+
+// Combat strength of units: sum of square root of hit points times damage (life time damage,
+// LTD2). Predicts the outcome of a fight better than material as it accounts for damage being
+// spread over a group of units. Buildings and resources are not counted.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct LTD2;
+
+impl Evaluation for LTD2 {
+    fn evaluate(&self, view: &GameView) -> f64 {
+        let mut mine = 0.0;
+        let mut enemy = 0.0;
+
+        for (_entity_id, entity) in view.entities() {
+            if let Object::Unit(_, ref unit) = entity.object {
+                let value = (unit.hit_points() as f64).sqrt() * unit.damage() as f64;
+                if view.is_mine(entity) {
+                    mine += value;
+                } else {
+                    enemy += value;
+                }
+            }
+        }
+
+        balance(mine, enemy)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use game::{Coordinates, Unit};
+    use game::test_support::TwoPlayers;
+
+    #[test]
+    fn evaluations() {
+        let players = TwoPlayers::default();
+        let (p1, p2) = (&players.p1, &players.p2);
+
+        let game = players.game(&[
+            (Coordinates(0, 0), Object::Building(p1, Building::Base(Resource(5)))),
+            (Coordinates(1, 1), Object::Unit(p1, Unit::Worker)),
+            (Coordinates(1, 2), Object::Unit(p1, Unit::Worker)),
+            (Coordinates(7, 7), Object::Building(p2, Building::Base(Resource(5)))),
+            (Coordinates(6, 6), Object::Unit(p2, Unit::Heavy)),
+        ]);
+        let (mario, luigi) = (game.view_for(p1), game.view_for(p2));
+
+        // Two workers cost as much as one Heavy
+        assert_eq!(Material.evaluate(&mario), 0.0);
+        // but are much weaker in a fight
+        assert_eq!(LTD2.evaluate(&mario), (2.0 - 8.0) / (2.0 + 8.0));
+        assert_eq!(LTD2.evaluate(&luigi), -LTD2.evaluate(&mario));
+
+        let custom = |view: &GameView| view.my_units().count() as f64;
+        assert_eq!(custom.evaluate(&mario), 2.0);
+    }
+}
@@ -1,30 +1,48 @@
 use std::fmt::Display;
 use std::fmt;
 use std::ptr;
+use std::collections::{HashMap, HashSet};
 use itertools::Itertools;
 
-use game::terrain::{Terrain, Direction, Coordinates, Location, Tile};
-use game::entity::{Entity, Object, Iter as EntitiesIter, EntitiesError, Unit, Building,
+use game::terrain::{Terrain, Direction, Connectivity, Coordinates, Location, Tile, AreaIter};
+use game::entity::{Entity, Object, Iter as EntitiesIter, InAreaIter as EntitiesInAreaIter, EntitiesError, Unit, Building,
                        Resource, Entities, EntityID};
 use game::player::{Player, Owned};
 use game_view::GameView;
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct GameState<'p, 't> {
     name: String,
     round: u32,
+    cycle: u64,
+    connectivity: Connectivity,
     terrain: &'t Terrain,
     entities: Entities<'p, 't>,
+    // Cycle at which entity has finished its last action and can be given new order
+    busy_until: HashMap<EntityID, u64>,
+    // Resources workers have harvested and not yet returned to a base
+    carrying: HashMap<EntityID, u64>,
 }
 
+// Cycles it takes worker to harvest and to return resources, and amount it carries at once
+const HARVEST_TIME: u64 = 2;
+const RETURN_TIME: u64 = 1;
+const HARVEST_AMOUNT: u64 = 1;
+
+const TRAINABLE_UNITS: [Unit; 3] = [Unit::Worker, Unit::Light, Unit::Heavy];
+
 //TODO: Error trait
 // This type cannot keep references to Game or Entity so it can be passed back to AI causing the
 // violation
 #[derive(Debug)]
 pub enum GameRuleViolation<'p, 't> {
     InvalidMove(EntityID, Direction, InvalidMove<'t>),
+    InvalidAction(Order, InvalidAction<'t>),
     EntityNotOwned(EntityID, &'p Player),
     EntityDoesNotExist(EntityID),
+    EntityBusy(EntityID, u64),
+    // Unit was already given an order this cycle
+    DuplicateOrder(EntityID),
 }
 
 #[derive(Debug)]
@@ -33,9 +51,59 @@ pub enum InvalidMove<'t> {
     LocationAlreadyTaken(Location<'t>, EntityID),
     Immovable,
     OutOfMap,
+    DirectionNotAllowed,
+    CornerCutting,
+}
+
+// Why order other than move cannot be executed
+#[derive(Debug)]
+pub enum InvalidAction<'t> {
+    // Entity cannot give this kind of order, e.g. Light harvesting or base training Heavy
+    NotCapable,
+    OutOfMap,
+    DirectionNotAllowed,
+    NoResources(Location<'t>),
+    NoOwnBase(Location<'t>),
+    AlreadyCarrying,
+    NothingCarried,
+    NotWalkable(Location<'t>),
+    NotBuildable(Location<'t>),
+    LocationAlreadyTaken(Location<'t>, EntityID),
+    // Player's bases hold less resources than the cost
+    CannotAfford(u64),
 }
 
 impl<'p, 't> GameState<'p, 't> {
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn round(&self) -> u32 {
+        self.round
+    }
+
+    pub fn cycle(&self) -> u64 {
+        self.cycle
+    }
+
+    pub fn terrain(&self) -> &'t Terrain {
+        self.terrain
+    }
+
+    pub fn connectivity(&self) -> Connectivity {
+        self.connectivity
+    }
+
+    // Entity is busy while it is executing durative action (e.g. moving onto rough terrain)
+    pub fn is_busy(&self, entity_id: EntityID) -> bool {
+        self.busy_for(entity_id) > 0
+    }
+
+    // Number of cycles until entity can be given new order
+    pub fn busy_for(&self, entity_id: EntityID) -> u64 {
+        self.busy_until.get(&entity_id).map(|&until| until.saturating_sub(self.cycle)).unwrap_or(0)
+    }
+
     pub fn view_for<'s>(&'s self, player: &'p Player) -> GameView<'p, 't, 's> {
         GameView::new(self, player)
     }
@@ -44,85 +112,398 @@ impl<'p, 't> GameState<'p, 't> {
         self.entities.iter()
     }
 
+    pub fn entities_in_area<'s>(&'s self, area: AreaIter<'t>) -> EntitiesInAreaIter<'p, 't, 's> {
+        self.entities.in_area(area)
+    }
+
+    pub fn get_entity<'s>(&'s self, entity_id: EntityID) -> Option<&'s Entity<'t, 'p>> {
+        self.entities.get(entity_id)
+    }
+
     pub fn get_entity_by_location<'s> (&'s self, location: Location<'t>) -> Option<&'s Entity<'t, 'p>> {
         self.entities.get_by_location(location)
     }
 
-    fn move_entity(
-        &mut self,
+    // Resources the worker has harvested and not returned yet
+    pub fn carried(&self, entity_id: EntityID) -> u64 {
+        self.carrying.get(&entity_id).cloned().unwrap_or(0)
+    }
+
+    // Resources stored in player's bases; training and building is paid from them
+    pub fn resources_of(&self, player: &Player) -> u64 {
+        self.entities()
+            .filter_map(|(_, entity)| match entity.object {
+                Object::Building(owner, Building::Base(Resource(amount))) if ptr::eq(owner, player) => Some(amount),
+                _ => None,
+            })
+            .sum()
+    }
+
+    // Takes cost from player's bases in order of their IDs
+    fn pay(&mut self, player: &Player, cost: u64) {
+        let mut bases = self.entities()
+            .filter(|&(_, entity)| match entity.object {
+                Object::Building(owner, Building::Base(_)) => ptr::eq(owner, player),
+                _ => false,
+            })
+            .map(|(entity_id, _)| entity_id)
+            .collect::<Vec<_>>();
+        bases.sort_by_key(|entity_id| entity_id.0);
+
+        let mut left = cost;
+        for entity_id in bases {
+            let base = self.entities.get_mutator(entity_id).expect("base does not exist");
+            if let Object::Building(_, Building::Base(Resource(ref mut amount))) = base.entity.object {
+                let taken = left.min(*amount);
+                *amount -= taken;
+                left -= taken;
+            }
+        }
+        assert_eq!(left, 0, "player cannot afford the cost");
+    }
+
+    // Entity with given ID if it belongs to the player; resources belong to nobody
+    fn owned_entity(&self, player: &'p Player, entity_id: EntityID) -> Result<&Entity<'t, 'p>, GameRuleViolation<'p, 't>> {
+        let entity = match self.entities.get(entity_id) {
+            Some(entity) => entity,
+            None => return Err(GameRuleViolation::EntityDoesNotExist(entity_id)),
+        };
+
+        match entity.object {
+            Object::Unit(owner, _) |
+            Object::Building(owner, _) if ptr::eq(owner, player) => Ok(entity),
+            _ => Err(GameRuleViolation::EntityNotOwned(entity_id, player)),
+        }
+    }
+
+    // Checks if player could move entity in given direction right now returning location the
+    // entity would end up at. Ownership is checked first so that nothing else is revealed about
+    // entities of other players.
+    pub fn check_move(
+        &self,
         player: &'p Player,
         entity_id: EntityID,
         direction: Direction,
-    ) -> Result<(), GameRuleViolation<'p, 't>> {
-        if let Some(ref mut entity_mutator) = self.entities.get_mutator(entity_id) {
-            let current_location = match entity_mutator.entity.object {
-                Object::Building(..) |
-                Object::Resources(..) => {
-                    return Err(GameRuleViolation::InvalidMove(
-                        entity_mutator.entity.id,
-                        direction,
-                        InvalidMove::Immovable,
-                    ))
+    ) -> Result<Location<'t>, GameRuleViolation<'p, 't>> {
+        let entity = self.owned_entity(player, entity_id)?;
+
+        if self.is_busy(entity_id) {
+            return Err(GameRuleViolation::EntityBusy(entity_id, self.busy_until[&entity_id]));
+        }
+
+        let invalid_move = |invalid_move| GameRuleViolation::InvalidMove(entity_id, direction, invalid_move);
+
+        if !self.connectivity.allows(direction) {
+            return Err(invalid_move(InvalidMove::DirectionNotAllowed));
+        }
+
+        let current_location = match entity.object {
+            Object::Building(..) |
+            Object::Resources(..) => return Err(invalid_move(InvalidMove::Immovable)),
+            Object::Unit(..) => entity.location,
+        };
+
+        let new_location = match current_location.in_direction(direction) {
+            None => return Err(invalid_move(InvalidMove::OutOfMap)),
+            Some(new_location) => new_location,
+        };
+
+        if current_location.cuts_corner(direction) {
+            return Err(invalid_move(InvalidMove::CornerCutting));
+        }
+
+        if !new_location.walkable() {
+            return Err(invalid_move(InvalidMove::NotWalkable(new_location)));
+        }
+
+        if let Some(other) = self.entities.get_by_location(new_location) {
+            return Err(invalid_move(InvalidMove::LocationAlreadyTaken(new_location, other.id)));
+        }
+
+        Ok(new_location)
+    }
+
+    // Checks if player could give the order right now returning location the order targets: the
+    // location entity moves to, harvests from, returns to or places new entity on. As with moves
+    // ownership is checked first.
+    pub fn check_order(&self, player: &'p Player, order: Order) -> Result<Location<'t>, GameRuleViolation<'p, 't>> {
+        let (entity_id, direction) = (order.entity_id(), order.direction());
+        if let Order::Move(..) = order {
+            return self.check_move(player, entity_id, direction);
+        }
+
+        let entity = self.owned_entity(player, entity_id)?;
+
+        if self.is_busy(entity_id) {
+            return Err(GameRuleViolation::EntityBusy(entity_id, self.busy_until[&entity_id]));
+        }
+
+        let invalid_action = |invalid_action| GameRuleViolation::InvalidAction(order, invalid_action);
+
+        let capable = match (order, &entity.object) {
+            (Order::Harvest(..), &Object::Unit(_, Unit::Worker)) |
+            (Order::Return(..), &Object::Unit(_, Unit::Worker)) |
+            (Order::Build(..), &Object::Unit(_, Unit::Worker)) => true,
+            (Order::Train(_, _, unit), Object::Building(_, building)) => building.can_train(unit),
+            _ => false,
+        };
+        if !capable {
+            return Err(invalid_action(InvalidAction::NotCapable));
+        }
+
+        if !self.connectivity.allows(direction) {
+            return Err(invalid_action(InvalidAction::DirectionNotAllowed));
+        }
+
+        let target = match entity.location.in_direction(direction) {
+            None => return Err(invalid_action(InvalidAction::OutOfMap)),
+            Some(target) => target,
+        };
+        let occupant = self.entities.get_by_location(target);
+
+        match order {
+            Order::Harvest(..) => {
+                if self.carried(entity_id) > 0 {
+                    return Err(invalid_action(InvalidAction::AlreadyCarrying));
                 }
-                Object::Unit(owner, _) => {
-                    if !ptr::eq(owner, player) {
-                        return Err(GameRuleViolation::EntityNotOwned(
-                            entity_mutator.entity.id,
-                            player,
-                        ));
+                match occupant.map(|occupant| &occupant.object) {
+                    Some(&Object::Resources(..)) => (),
+                    _ => return Err(invalid_action(InvalidAction::NoResources(target))),
+                }
+            }
+            Order::Return(..) => {
+                if self.carried(entity_id) == 0 {
+                    return Err(invalid_action(InvalidAction::NothingCarried));
+                }
+                match occupant.map(|occupant| &occupant.object) {
+                    Some(&Object::Building(owner, Building::Base(_))) if ptr::eq(owner, player) => (),
+                    _ => return Err(invalid_action(InvalidAction::NoOwnBase(target))),
+                }
+            }
+            Order::Train(..) | Order::Build(..) => {
+                if !target.walkable() {
+                    return Err(invalid_action(InvalidAction::NotWalkable(target)));
+                }
+                if let Order::Build(..) = order {
+                    if !target.buildable() {
+                        return Err(invalid_action(InvalidAction::NotBuildable(target)));
                     }
-                    entity_mutator.entity.location
                 }
-            };
+                if let Some(other) = occupant {
+                    return Err(invalid_action(InvalidAction::LocationAlreadyTaken(target, other.id)));
+                }
 
-            let new_location = match current_location.in_direction(direction) {
-                None => {
-                    return Err(GameRuleViolation::InvalidMove(
-                        entity_mutator.entity.id,
-                        direction,
-                        InvalidMove::OutOfMap,
-                    ))
+                let cost = match order {
+                    Order::Train(_, _, unit) => unit.cost(),
+                    _ => Building::Barracks.cost(),
+                };
+                if self.resources_of(player) < cost {
+                    return Err(invalid_action(InvalidAction::CannotAfford(cost)));
                 }
-                Some(new_location) => new_location,
-            };
+            }
+            Order::Move(..) => unreachable!("moves are checked by check_move"),
+        }
 
-            entity_mutator.set_location(new_location).map_err(
-                |err| match err {
-                    EntitiesError::LocationNotWalkable(new_location) => {
-                        GameRuleViolation::InvalidMove(
-                            entity_id,
-                            direction,
-                            InvalidMove::NotWalkable(new_location),
-                        )
-                    }
-                    EntitiesError::LocationAlreadyOccupied(new_location, by_entity_id) => {
-                        GameRuleViolation::InvalidMove(
-                            entity_id,
-                            direction,
-                            InvalidMove::LocationAlreadyTaken(new_location, by_entity_id),
-                        )
+        Ok(target)
+    }
+
+    // Orders for given entity that player could give right now without violating game rules;
+    // moves come first
+    pub fn legal_orders(&self, player: &'p Player, entity_id: EntityID) -> Vec<Order> {
+        let directions = self.connectivity.directions().collect::<Vec<_>>();
+        let moves = directions.iter().map(|&direction| Order::Move(entity_id, direction));
+        let actions = directions.iter().flat_map(|&direction| {
+            vec![Order::Harvest(entity_id, direction), Order::Return(entity_id, direction), Order::Build(entity_id, direction)]
+                .into_iter()
+                .chain(TRAINABLE_UNITS.iter().map(move |&unit| Order::Train(entity_id, direction, unit)))
+        });
+
+        moves.chain(actions)
+            .filter(|&order| self.check_order(player, order).is_ok())
+            .collect()
+    }
+
+    fn execute(&mut self, player: &'p Player, order: Order) -> Result<(), GameRuleViolation<'p, 't>> {
+        let target = self.check_order(player, order)?;
+        let entity_id = order.entity_id();
+
+        let busy_for = match order {
+            Order::Move(..) => {
+                self.entities
+                    .get_mutator(entity_id)
+                    .expect("moved entity does not exist")
+                    .set_location(target)
+                    .expect("checked move failed");
+
+                // Moving takes as many cycles as it costs to enter new location
+                target.movement_cost().expect("entered location not walkable")
+            }
+            Order::Harvest(..) => {
+                let resources_id = self.entities.get_by_location(target).expect("no resources to harvest").id;
+                let left = {
+                    let resources = self.entities.get_mutator(resources_id).expect("resources do not exist");
+                    match resources.entity.object {
+                        Object::Resources(Resource(ref mut amount)) => {
+                            let taken = HARVEST_AMOUNT.min(*amount);
+                            *amount -= taken;
+                            self.carrying.insert(entity_id, taken);
+                            *amount
+                        }
+                        _ => unreachable!("checked harvest not from resources"),
                     }
-                },
-            )
-        } else {
-            return Err(GameRuleViolation::EntityDoesNotExist(entity_id));
-        }
+                };
+
+                // Depleted patch disappears
+                if left == 0 {
+                    self.entities.remove(resources_id);
+                }
+                HARVEST_TIME
+            }
+            Order::Return(..) => {
+                let carried = self.carrying.remove(&entity_id).unwrap_or(0);
+                let base_id = self.entities.get_by_location(target).expect("no base to return to").id;
+                let base = self.entities.get_mutator(base_id).expect("base does not exist");
+                if let Object::Building(_, Building::Base(Resource(ref mut amount))) = base.entity.object {
+                    *amount += carried;
+                }
+                RETURN_TIME
+            }
+            // New entity takes its place right away but cannot act until it is finished
+            Order::Train(_, _, unit) => {
+                self.pay(player, unit.cost());
+                let trained = self.entities.place(target, Object::Unit(player, unit)).expect("checked training failed");
+                self.busy_until.insert(trained, self.cycle + unit.production_time());
+                unit.production_time()
+            }
+            Order::Build(..) => {
+                let barracks = Building::Barracks;
+                self.pay(player, barracks.cost());
+                let time = barracks.production_time();
+                let built = self.entities.place(target, Object::Building(player, barracks)).expect("checked building failed");
+                self.busy_until.insert(built, self.cycle + time);
+                time
+            }
+        };
+
+        self.busy_until.insert(entity_id, self.cycle + busy_for);
+        Ok(())
     }
 
-    pub fn apply<A>(&mut self, desires: A)
+    // Applies orders for one game cycle returning orders that could not be executed.
+    //
+    // Each entity can be given at most one order per cycle: the first order given by the owner
+    // wins, even if it could not be executed, and all following orders for that entity are
+    // rejected with DuplicateOrder. Orders given for entities the player does not own do not count.
+    pub fn apply<A>(&mut self, desires: A) -> Vec<GameRuleViolation<'p, 't>>
     where
         A: Iterator<Item = Owned<'p, Order>>,
     {
+        let mut violations = Vec::new();
+        let mut ordered = HashSet::new();
+
         for Owned(player, desire) in desires {
-            println!("{}: {:?}", player.name, desire);
-            match desire {
-                Order::Move(entity_id, direction) => {
-                    self.move_entity(player, entity_id, direction).expect(
-                        "TODO: collect rule violations and pass to AI",
-                    )
-                }
+            let entity_id = desire.entity_id();
+
+            // Orders for entities of other players are rejected before they can take the
+            // owner's order for this cycle
+            if let Err(violation) = self.owned_entity(player, entity_id) {
+                violations.push(violation);
+                continue;
+            }
+
+            if !ordered.insert(entity_id) {
+                violations.push(GameRuleViolation::DuplicateOrder(entity_id));
+                continue;
+            }
+
+            if let Err(violation) = self.execute(player, desire) {
+                violations.push(violation);
             }
         }
+
+        self.cycle += 1;
+        violations
+    }
+}
+
+// Copy of game state that borrows neither terrain nor players so it can be sent to a thread that
+// may outlive them. Owners are kept as positions in the list of players the snapshot was taken
+// with.
+#[derive(Debug, Clone)]
+pub struct Snapshot {
+    name: String,
+    round: u32,
+    cycle: u64,
+    connectivity: Connectivity,
+    // ID, location index and object of every entity
+    entities: Vec<(EntityID, usize, SnapshotObject)>,
+    next_id: usize,
+    busy_until: HashMap<EntityID, u64>,
+    carrying: HashMap<EntityID, u64>,
+}
+
+#[derive(Debug, Clone)]
+enum SnapshotObject {
+    Unit(usize, Unit),
+    Building(usize, Building),
+    Resources(Resource),
+}
+
+impl<'p, 't> GameState<'p, 't> {
+    // Panics if an entity is owned by player that is not listed
+    pub fn snapshot(&self, players: &[&'p Player]) -> Snapshot {
+        let position = |owner: &Player| {
+            players.iter().position(|&player| ptr::eq(player, owner)).expect("owner not listed")
+        };
+
+        Snapshot {
+            name: self.name.clone(),
+            round: self.round,
+            cycle: self.cycle,
+            connectivity: self.connectivity,
+            entities: self.entities()
+                .map(|(entity_id, entity)| {
+                    let object = match entity.object {
+                        Object::Unit(owner, unit) => SnapshotObject::Unit(position(owner), unit),
+                        Object::Building(owner, ref building) => SnapshotObject::Building(position(owner), building.clone()),
+                        Object::Resources(ref resource) => SnapshotObject::Resources(resource.clone()),
+                    };
+                    (entity_id, entity.location.index(), object)
+                })
+                .collect(),
+            next_id: self.entities.next_id(),
+            busy_until: self.busy_until.clone(),
+            carrying: self.carrying.clone(),
+        }
+    }
+}
+
+impl Snapshot {
+    // Game state on given terrain, which has to be a copy of the one the snapshot was taken on,
+    // with owners taken from players listed in the same order
+    pub fn restore<'p, 't>(&self, terrain: &'t Terrain, players: &[&'p Player]) -> GameState<'p, 't> {
+        let mut entities = Entities::new();
+        for &(entity_id, index, ref object) in &self.entities {
+            let location = terrain.location_by_index(index).expect("snapshot of other terrain");
+            let object = match *object {
+                SnapshotObject::Unit(owner, unit) => Object::Unit(players[owner], unit),
+                SnapshotObject::Building(owner, ref building) => Object::Building(players[owner], building.clone()),
+                SnapshotObject::Resources(ref resource) => Object::Resources(resource.clone()),
+            };
+            entities.restore(entity_id, location, object);
+        }
+        entities.skip_ids(self.next_id);
+
+        GameState {
+            name: self.name.clone(),
+            round: self.round,
+            cycle: self.cycle,
+            connectivity: self.connectivity,
+            terrain,
+            entities,
+            busy_until: self.busy_until.clone(),
+            carrying: self.carrying.clone(),
+        }
     }
 }
 
@@ -136,6 +517,7 @@ pub enum GameStateBuilderError<'t> {
 #[derive(Debug)]
 pub struct GameStateBuilder<'p, 't> {
     name: String,
+    connectivity: Connectivity,
     terrain: &'t Terrain,
     entities: Entities<'p, 't>,
 }
@@ -144,46 +526,90 @@ impl<'p, 't> GameStateBuilder<'p, 't> {
     pub fn new<N: Into<String>>(name: N, terrain: &'t Terrain) -> GameStateBuilder<'p, 't> {
         GameStateBuilder {
             name: name.into(),
-            terrain: terrain,
+            connectivity: Connectivity::default(),
+            terrain,
             entities: Entities::new(),
         }
     }
 
+    pub fn connectivity(&mut self, connectivity: Connectivity) -> &mut GameStateBuilder<'p, 't> {
+        self.connectivity = connectivity;
+        self
+    }
+
     pub fn place(&mut self, coordinates: Coordinates, object: Object<'p>) -> Result<&mut GameStateBuilder<'p, 't>, GameStateBuilderError<'t>> {
         self.terrain.location(coordinates)
-            .ok_or_else(|| GameStateBuilderError::OutOfMap(coordinates))
+            .ok_or(GameStateBuilderError::OutOfMap(coordinates))
             .and_then(|location| self.entities.place(location, object)
-                      .map_err(|place_error| GameStateBuilderError::EntityPlaceError(place_error)))
+                      .map_err(GameStateBuilderError::EntityPlaceError))
             .map(|_| self)
     }
 
     pub fn build_for_round(&self, round: u32) -> GameState<'p, 't> {
         GameState {
             name: self.name.clone(),
-            round: round,
-            terrain: self.terrain.clone(),
+            round,
+            cycle: 0,
+            connectivity: self.connectivity,
+            terrain: self.terrain,
             entities: self.entities.clone(),
+            busy_until: HashMap::new(),
+            carrying: HashMap::new(),
         }
     }
 }
 
-#[derive(Debug)]
+// Every order acts on the location next to the entity in given direction
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy)]
 pub enum Order {
     Move(EntityID, Direction),
+    // Worker takes resources from the patch
+    Harvest(EntityID, Direction),
+    // Worker puts resources it carries into own base
+    Return(EntityID, Direction),
+    // Building trains unit onto free location; paid from player's bases
+    Train(EntityID, Direction, Unit),
+    // Worker builds barracks on free location; paid from player's bases
+    Build(EntityID, Direction),
 }
 
-const GRID_INTERSECTION: &'static str = "+";
-const GRID_HOR_LINE: &'static str = "---";
-const GRID_VERT_LINE: &'static str = "|";
-const GRID_EMPTY: &'static str = "   ";
-const GRID_WALL: &'static str = "XXX";
+impl Order {
+    pub fn entity_id(&self) -> EntityID {
+        match *self {
+            Order::Move(entity_id, _) |
+            Order::Harvest(entity_id, _) |
+            Order::Return(entity_id, _) |
+            Order::Train(entity_id, _, _) |
+            Order::Build(entity_id, _) => entity_id,
+        }
+    }
 
-const ENTITY_WORKER: &'static str = "W";
-const ENTITY_LIGHT: &'static str = "L";
-const ENTITY_HEAVY: &'static str = "H";
-const ENTITY_BASE: &'static str = "@";
-const ENTITY_BARRACS: &'static str = "B";
-const ENTITY_RESOURCES: &'static str = "#";
+    pub fn direction(&self) -> Direction {
+        match *self {
+            Order::Move(_, direction) |
+            Order::Harvest(_, direction) |
+            Order::Return(_, direction) |
+            Order::Train(_, direction, _) |
+            Order::Build(_, direction) => direction,
+        }
+    }
+}
+
+const GRID_INTERSECTION: &str = "+";
+const GRID_HOR_LINE: &str = "---";
+const GRID_VERT_LINE: &str = "|";
+const GRID_EMPTY: &str = "   ";
+const GRID_WALL: &str = "XXX";
+const GRID_ROUGH: &str = " . ";
+const GRID_WATER: &str = "~~~";
+const GRID_BRIDGE: &str = "===";
+
+const ENTITY_WORKER: &str = "W";
+const ENTITY_LIGHT: &str = "L";
+const ENTITY_HEAVY: &str = "H";
+const ENTITY_BASE: &str = "@";
+const ENTITY_BARRACS: &str = "B";
+const ENTITY_RESOURCES: &str = "#";
 
 impl<'p, 't> Display for GameState<'p, 't> {
     fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
@@ -215,32 +641,33 @@ impl<'p, 't> Display for GameState<'p, 't> {
             writeln!(f)?;
             for location in row {
                 write!(f, "{}", GRID_VERT_LINE)?;
-                match location.tile {
-                    &Tile::Wall => write!(f, "{}", GRID_WALL)?,
-                    &Tile::Empty => {
+                match *location.tile {
+                    Tile::Wall => write!(f, "{}", GRID_WALL)?,
+                    Tile::Water => write!(f, "{}", GRID_WATER)?,
+                    Tile::Empty | Tile::Rough | Tile::Bridge => {
                         //TODO: merge join entities (ordered by coord, next() for peek().coord ==
                         // tile.coord
-                        if let Some(ref entity) = self.entities.get_by_location(location) {
+                        if let Some(entity) = self.entities.get_by_location(location) {
                             match entity.object {
-                                Object::Unit(ref player, Unit::Worker) => {
+                                Object::Unit(player, Unit::Worker) => {
                                     write_owned_entity(f, player, entity.id, ENTITY_WORKER)?
                                 }
-                                Object::Unit(ref player, Unit::Light) => {
+                                Object::Unit(player, Unit::Light) => {
                                     write_owned_entity(f, player, entity.id, ENTITY_LIGHT)?
                                 }
 
-                                Object::Unit(ref player, Unit::Heavy) => {
+                                Object::Unit(player, Unit::Heavy) => {
                                     write_owned_entity(f, player, entity.id, ENTITY_HEAVY)?
                                 }
 
-                                Object::Building(ref player, Building::Base(Resource(res))) => {
+                                Object::Building(player, Building::Base(Resource(res))) => {
                                     write!(
                                         f,
                                         "{}",
                                         player.colour.paint(format!("{}{:02}", ENTITY_BASE, res))
                                     )?
                                 }
-                                Object::Building(ref player, Building::Barracks) => {
+                                Object::Building(player, Building::Barracks) => {
                                     write_owned_entity(f, player, entity.id, ENTITY_BARRACS)?
                                 }
                                 Object::Resources(Resource(res)) => {
@@ -248,7 +675,11 @@ impl<'p, 't> Display for GameState<'p, 't> {
                                 }
                             }
                         } else {
-                            write!(f, "{}", GRID_EMPTY)?
+                            match *location.tile {
+                                Tile::Rough => write!(f, "{}", GRID_ROUGH)?,
+                                Tile::Bridge => write!(f, "{}", GRID_BRIDGE)?,
+                                _ => write!(f, "{}", GRID_EMPTY)?,
+                            }
                         }
                     }
                 }
@@ -260,3 +691,292 @@ impl<'p, 't> Display for GameState<'p, 't> {
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use game::terrain::{TerrainBuilder, Dimension};
+    use game::test_support::TwoPlayers;
+    use game::InvalidAction;
+
+    #[test]
+    fn move_onto_rough_terrain_takes_longer() {
+        let terrain = TerrainBuilder::new(Dimension::new(4).unwrap(), Dimension::new(1).unwrap())
+            .place(Coordinates(1, 0), Tile::Rough).unwrap()
+            .place(Coordinates(3, 0), Tile::Water).unwrap()
+            .build();
+        let players = TwoPlayers::on(terrain);
+        let player = &players.p1;
+
+        let mut game = players.game(&[(Coordinates(0, 0), Object::Unit(player, Unit::Worker))]);
+        let worker = game.entities().next().unwrap().0;
+
+        let mv = || Some(Owned(player, Order::Move(worker, Direction::Right))).into_iter();
+
+        // entering rough tile costs 2 cycles
+        assert!(game.apply(mv()).is_empty());
+        assert!(game.is_busy(worker));
+        match game.apply(mv()).as_slice() {
+            &[GameRuleViolation::EntityBusy(entity_id, 2)] => assert_eq!(entity_id, worker),
+            violations => panic!("unexpected violations: {:?}", violations),
+        }
+        assert!(!game.is_busy(worker));
+        assert!(game.apply(mv()).is_empty());
+
+        // water is impassable
+        match game.apply(mv()).as_slice() {
+            &[GameRuleViolation::InvalidMove(_, Direction::Right, InvalidMove::NotWalkable(_))] => (),
+            violations => panic!("unexpected violations: {:?}", violations),
+        }
+    }
+
+    #[test]
+    fn bridges_can_be_crossed_but_not_built_on() {
+        let terrain = TerrainBuilder::new(Dimension::new(3).unwrap(), Dimension::new(1).unwrap())
+            .place(Coordinates(1, 0), Tile::Bridge).unwrap()
+            .build();
+        let player = Player::new("Mario", ::ansi_term::Colour::Red);
+
+        let mut builder = GameStateBuilder::new("test", &terrain);
+        match builder.place(Coordinates(1, 0), Object::Building(&player, Building::Barracks)) {
+            Err(GameStateBuilderError::EntityPlaceError(EntitiesError::LocationNotBuildable(_))) => (),
+            result => panic!("unexpected result: {:?}", result.map(|_| ())),
+        }
+        assert!(builder.place(Coordinates(1, 0), Object::Resources(Resource(5))).is_err());
+
+        builder.place(Coordinates(0, 0), Object::Unit(&player, Unit::Worker)).unwrap();
+        let mut game = builder.build_for_round(0);
+        let worker = game.entities().next().unwrap().0;
+
+        // entering bridge costs as much as empty tile
+        let mv = || Some(Owned(&player, Order::Move(worker, Direction::Right))).into_iter();
+        assert!(game.apply(mv()).is_empty());
+        assert!(game.apply(mv()).is_empty());
+        assert_eq!(game.get_entity(worker).unwrap().location.coordinates, Coordinates(2, 0));
+    }
+
+    #[test]
+    fn diagonal_moves_follow_connectivity() {
+        let players = TwoPlayers::default();
+        let player = &players.p1;
+
+        let mut builder = players.builder(&[(Coordinates(3, 3), Object::Unit(player, Unit::Worker))]);
+
+        let mut game = builder.build_for_round(0);
+        let worker = game.entities().next().unwrap().0;
+        match game.apply(Some(Owned(player, Order::Move(worker, Direction::UpLeft))).into_iter()).as_slice() {
+            &[GameRuleViolation::InvalidMove(_, Direction::UpLeft, InvalidMove::DirectionNotAllowed)] => (),
+            violations => panic!("unexpected violations: {:?}", violations),
+        }
+
+        builder.connectivity(Connectivity::Eight);
+        let mut game = builder.build_for_round(0);
+        match game.apply(Some(Owned(player, Order::Move(worker, Direction::DownRight))).into_iter()).as_slice() {
+            &[GameRuleViolation::InvalidMove(_, Direction::DownRight, InvalidMove::CornerCutting)] => (),
+            violations => panic!("unexpected violations: {:?}", violations),
+        }
+        assert!(game.apply(Some(Owned(player, Order::Move(worker, Direction::UpLeft))).into_iter()).is_empty());
+        assert_eq!(game.entities().next().unwrap().1.location.coordinates, Coordinates(2, 2));
+    }
+
+    #[test]
+    fn moves_of_other_players_entities_are_not_checked() {
+        let terrain = TerrainBuilder::new(Dimension::new(4).unwrap(), Dimension::new(2).unwrap())
+            .place(Coordinates(1, 0), Tile::Rough).unwrap()
+            .build();
+        let players = TwoPlayers::on(terrain);
+        let (p1, p2) = (&players.p1, &players.p2);
+
+        let mut game = players.game(&[
+            (Coordinates(0, 0), Object::Unit(p1, Unit::Worker)),
+            (Coordinates(3, 1), Object::Resources(Resource(5))),
+        ]);
+        let worker = game.entities().find(|&(_, entity)| entity.location.coordinates == Coordinates(0, 0)).unwrap().0;
+        let resources = game.entities().find(|&(_, entity)| entity.location.coordinates == Coordinates(3, 1)).unwrap().0;
+
+        // Worker is busy entering rough tile
+        assert!(game.apply(Some(Owned(p1, Order::Move(worker, Direction::Right))).into_iter()).is_empty());
+        match game.check_move(p1, worker, Direction::Right) {
+            Err(GameRuleViolation::EntityBusy(..)) => (),
+            result => panic!("unexpected result: {:?}", result),
+        }
+
+        for &direction in &[Direction::Right, Direction::DownRight] {
+            match game.check_move(p2, worker, direction) {
+                Err(GameRuleViolation::EntityNotOwned(..)) => (),
+                result => panic!("unexpected result: {:?}", result),
+            }
+        }
+        match game.check_move(p1, resources, Direction::Left) {
+            Err(GameRuleViolation::EntityNotOwned(..)) => (),
+            result => panic!("unexpected result: {:?}", result),
+        }
+    }
+
+    #[test]
+    fn illegal_orders_of_other_players_do_not_block_owner() {
+        let players = TwoPlayers::default();
+        let (p1, p2) = (&players.p1, &players.p2);
+
+        let mut game = players.game(&[(Coordinates(0, 0), Object::Unit(p1, Unit::Worker))]);
+        let worker = game.entities().next().unwrap().0;
+
+        // Diagonal move is not allowed under four-directional movement
+        let orders = vec![
+            Owned(p2, Order::Move(worker, Direction::DownRight)),
+            Owned(p1, Order::Move(worker, Direction::Right)),
+        ];
+        match game.apply(orders.into_iter()).as_slice() {
+            &[GameRuleViolation::EntityNotOwned(entity_id, _)] => assert_eq!(entity_id, worker),
+            violations => panic!("unexpected violations: {:?}", violations),
+        }
+        assert_eq!(game.get_entity(worker).unwrap().location.coordinates, Coordinates(1, 0));
+    }
+
+    #[test]
+    fn one_order_per_unit_per_cycle() {
+        let players = TwoPlayers::default();
+        let (p1, p2) = (&players.p1, &players.p2);
+
+        let mut game = players.game(&[(Coordinates(0, 0), Object::Unit(p1, Unit::Worker))]);
+        let worker = game.entities().next().unwrap().0;
+
+        // Order of other player does not take away owner's order
+        let orders = vec![
+            Owned(p2, Order::Move(worker, Direction::Down)),
+            Owned(p1, Order::Move(worker, Direction::Right)),
+            Owned(p1, Order::Move(worker, Direction::Right)),
+        ];
+        match game.apply(orders.into_iter()).as_slice() {
+            &[GameRuleViolation::EntityNotOwned(..), GameRuleViolation::DuplicateOrder(entity_id)] => {
+                assert_eq!(entity_id, worker)
+            }
+            violations => panic!("unexpected violations: {:?}", violations),
+        }
+        assert_eq!(game.get_entity(worker).unwrap().location.coordinates, Coordinates(1, 0));
+
+        // First order wins even if it fails
+        let orders = vec![
+            Owned(p1, Order::Move(worker, Direction::Up)),
+            Owned(p1, Order::Move(worker, Direction::Right)),
+        ];
+        match game.apply(orders.into_iter()).as_slice() {
+            &[GameRuleViolation::InvalidMove(_, Direction::Up, InvalidMove::OutOfMap), GameRuleViolation::DuplicateOrder(_)] => (),
+            violations => panic!("unexpected violations: {:?}", violations),
+        }
+        assert_eq!(game.get_entity(worker).unwrap().location.coordinates, Coordinates(1, 0));
+    }
+
+    #[test]
+    fn workers_harvest_and_return_resources() {
+        let players = TwoPlayers::default();
+        let (p1, p2) = (&players.p1, &players.p2);
+
+        let mut game = players.game(&[
+            (Coordinates(0, 0), Object::Resources(Resource(2))),
+            (Coordinates(1, 0), Object::Unit(p1, Unit::Worker)),
+            (Coordinates(2, 0), Object::Building(p1, Building::Base(Resource(0)))),
+            (Coordinates(1, 1), Object::Building(p2, Building::Base(Resource(0)))),
+        ]);
+        let worker = game.get_entity_by_location(players.location(1, 0)).unwrap().id;
+        let order = |order| Some(Owned(p1, order)).into_iter();
+
+        // Resources can only be returned to own base
+        match game.apply(order(Order::Return(worker, Direction::Right))).as_slice() {
+            &[GameRuleViolation::InvalidAction(_, InvalidAction::NothingCarried)] => (),
+            violations => panic!("unexpected violations: {:?}", violations),
+        }
+        assert!(game.legal_orders(p1, worker).contains(&Order::Harvest(worker, Direction::Left)));
+        assert!(game.apply(order(Order::Harvest(worker, Direction::Left))).is_empty());
+        assert_eq!(game.carried(worker), 1);
+        assert_eq!(game.busy_for(worker), HARVEST_TIME - 1);
+        game.apply(None.into_iter());
+        match game.apply(order(Order::Return(worker, Direction::Down))).as_slice() {
+            &[GameRuleViolation::InvalidAction(_, InvalidAction::NoOwnBase(_))] => (),
+            violations => panic!("unexpected violations: {:?}", violations),
+        }
+        assert!(game.apply(order(Order::Return(worker, Direction::Right))).is_empty());
+        assert_eq!(game.carried(worker), 0);
+        assert_eq!(game.resources_of(p1), 1);
+        assert_eq!(game.resources_of(p2), 0);
+
+        // Depleted patch disappears
+        assert!(game.apply(order(Order::Harvest(worker, Direction::Left))).is_empty());
+        assert!(game.get_entity_by_location(players.location(0, 0)).is_none());
+        assert_eq!(game.carried(worker), 1);
+    }
+
+    #[test]
+    fn snapshot_restores_game_on_copies() {
+        let players = TwoPlayers::default();
+        let (p1, p2) = (&players.p1, &players.p2);
+
+        let mut game = players.game(&[
+            (Coordinates(0, 0), Object::Resources(Resource(2))),
+            (Coordinates(1, 0), Object::Unit(p1, Unit::Worker)),
+            (Coordinates(6, 6), Object::Unit(p2, Unit::Heavy)),
+        ]);
+        let worker = game.get_entity_by_location(players.location(1, 0)).unwrap().id;
+        game.apply(Some(Owned(p1, Order::Harvest(worker, Direction::Left))).into_iter());
+        let snapshot = game.snapshot(&[p1, p2]);
+
+        let copies = TwoPlayers::default();
+        let (c1, c2) = (&copies.p1, &copies.p2);
+        let mut copy = snapshot.restore(&copies.terrain, &[c1, c2]);
+        assert_eq!(copy.to_string(), game.to_string());
+        assert_eq!(copy.cycle(), game.cycle());
+        assert_eq!(copy.carried(worker), 1);
+        assert_eq!(copy.busy_for(worker), game.busy_for(worker));
+        let heavy = copy.get_entity_by_location(copies.location(6, 6)).unwrap();
+        assert!(matches!(heavy.object, Object::Unit(owner, Unit::Heavy) if ptr::eq(owner, c2)));
+
+        // New entities get the same IDs in both
+        let base = Object::Building(p1, Building::Base(Resource(0)));
+        let copied_base = Object::Building(c1, Building::Base(Resource(0)));
+        assert_eq!(
+            game.entities.place(players.location(3, 3), base).unwrap(),
+            copy.entities.place(copies.location(3, 3), copied_base).unwrap()
+        );
+    }
+
+    #[test]
+    fn training_and_building_is_paid_from_bases() {
+        let players = TwoPlayers::default();
+        let p1 = &players.p1;
+
+        let mut game = players.game(&[
+            (Coordinates(0, 0), Object::Building(p1, Building::Base(Resource(3)))),
+            (Coordinates(0, 2), Object::Building(p1, Building::Base(Resource(4)))),
+            (Coordinates(2, 0), Object::Unit(p1, Unit::Worker)),
+        ]);
+        let base = game.get_entity_by_location(players.location(0, 0)).unwrap().id;
+        let worker = game.get_entity_by_location(players.location(2, 0)).unwrap().id;
+        let order = |order| Some(Owned(p1, order)).into_iter();
+
+        // Base only trains workers, onto free location
+        match game.apply(order(Order::Train(base, Direction::Right, Unit::Light))).as_slice() {
+            &[GameRuleViolation::InvalidAction(_, InvalidAction::NotCapable)] => (),
+            violations => panic!("unexpected violations: {:?}", violations),
+        }
+        assert!(game.apply(order(Order::Train(base, Direction::Right, Unit::Worker))).is_empty());
+        let trained = game.get_entity_by_location(players.location(1, 0)).unwrap().id;
+        assert_eq!(game.busy_for(base), Unit::Worker.production_time() - 1);
+        assert_eq!(game.busy_for(trained), Unit::Worker.production_time() - 1);
+        assert_eq!(game.resources_of(p1), 6);
+
+        // Barracks cost takes resources of both bases
+        assert!(game.apply(order(Order::Build(worker, Direction::Down))).is_empty());
+        assert_eq!(game.resources_of(p1), 1);
+        let barracks = game.get_entity_by_location(players.location(2, 1)).unwrap().id;
+        assert_eq!(game.busy_for(barracks), Building::Barracks.production_time() - 1);
+
+        for _ in 1..Building::Barracks.production_time() {
+            game.apply(None.into_iter());
+        }
+        assert_eq!(game.legal_orders(p1, barracks), Vec::new());
+        match game.apply(order(Order::Train(barracks, Direction::Down, Unit::Heavy))).as_slice() {
+            &[GameRuleViolation::InvalidAction(_, InvalidAction::CannotAfford(2))] => (),
+            violations => panic!("unexpected violations: {:?}", violations),
+        }
+    }
+}
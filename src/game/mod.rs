@@ -2,10 +2,15 @@ mod terrain;
 mod entity;
 mod player;
 mod game_state;
+pub mod evaluation;
+#[cfg(test)]
+pub mod test_support;
 
 // Flat structure for AI
-pub use game::terrain::{Terrain, TerrainBuilder, TerrainBuilderError, Dimension, Direction, Coordinates, Location, Tile};
-pub use game::entity::{Entity, Object, Iter as EntitiesIter, EntitiesError, Unit, Building,
+pub use game::terrain::{Terrain, TerrainBuilder, TerrainBuilderError, Dimension, Direction,
+                        Connectivity, Metric, Coordinates, Location, Tile, AreaIter};
+pub use game::entity::{Entity, Object, Iter as EntitiesIter, InAreaIter as EntitiesInAreaIter, EntitiesError, Unit, Building,
                        Resource, Entities, EntityID};
-pub use game::player::{Player, Colour, AI, EmptyPersistentState, Owned};
-pub use game::game_state::{GameState, GameStateBuilder, Order};
\ No newline at end of file
+pub use game::player::{Player, Colour, AI, AnytimeAI, EmptyPersistentState, Owned};
+pub use game::game_state::{GameState, GameStateBuilder, GameStateBuilderError, GameRuleViolation,
+                           InvalidMove, InvalidAction, Order, Snapshot};
\ No newline at end of file
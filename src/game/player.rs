@@ -1,9 +1,11 @@
 pub use ansi_term::Colour;
 
+use std::time::Instant;
+
 use game::game_state::Order;
 use game_view::GameView;
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct Player {
     pub name: String,
     pub colour: Colour,
@@ -13,7 +15,7 @@ impl Player {
     pub fn new<N: Into<String>>(name: N, colour: Colour) -> Player {
         Player {
             name: name.into(),
-            colour: colour,
+            colour,
         }
     }
 
@@ -28,7 +30,7 @@ impl Player {
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy)]
 pub struct Owned<'p, T>(pub &'p Player, pub T);
 
 pub trait AI: Default {
@@ -45,5 +47,23 @@ pub trait AI: Default {
     ) -> Vec<Order>;
 }
 
+// AI that can be stopped at any time with its best orders so far. Search state is kept in self
+// so thinking continues across calls and from one cycle to the next.
+pub trait AnytimeAI: Default {
+    // Struct to keep data between game rounds
+    type PersistentState: Default;
+
+    // Called at the start of a round and whenever the game is not the one AI saw in the last
+    // cycle; drops any search in progress
+    fn start(&mut self, sate: &mut Self::PersistentState, view: &GameView);
+
+    // Thinks until deadline or until more thinking would not change the orders; view is of the
+    // current cycle so search has to follow the game as it moves on
+    fn improve(&mut self, sate: &mut Self::PersistentState, view: &GameView, deadline: Instant);
+
+    // Orders found so far; should be quick to return
+    fn best(&mut self, sate: &mut Self::PersistentState, view: &GameView) -> Vec<Order>;
+}
+
 #[derive(Default)]
 pub struct EmptyPersistentState;
@@ -1,11 +1,9 @@
-use std::ptr;
+use std::collections::hash_map::DefaultHasher;
 use std::hash::{Hash, Hasher};
+use std::fmt;
 use std::slice::Iter as SliceIter;
-use std::iter::Zip as ZipIter;
-use std::iter::Map as MapIter;
+use std::slice::Chunks;
 use std::iter::Enumerate;
-use std::option::IntoIter as OptionIntoIter;
-use std::iter::Cycle;
 
 #[derive(Debug, PartialEq, Eq, Hash, Clone, Copy)]
 pub struct Coordinates(pub usize, pub usize);
@@ -14,12 +12,16 @@ impl Coordinates {
     pub fn in_direction(&self, direction: Direction) -> Option<Coordinates> {
         match direction {
             Direction::Up if self.1 > 0 => Some(Coordinates(self.0, self.1 - 1)),
-            Direction::Right if self.0 < usize::max_value() => {
+            Direction::Right if self.0 < usize::MAX => {
                 Some(Coordinates(self.0 + 1, self.1))
             }
-            Direction::Down if self.1 < usize::max_value() => Some(Coordinates(self.0, self.1 + 1)),
+            Direction::Down if self.1 < usize::MAX => Some(Coordinates(self.0, self.1 + 1)),
             Direction::Left if self.0 > 0 => Some(Coordinates(self.0 - 1, self.1)),
-            _ => None,
+            Direction::Up | Direction::Right | Direction::Down | Direction::Left => None,
+            diagonal => {
+                let (vertical, horizontal) = diagonal.components().expect("diagonal direction");
+                self.in_direction(vertical).and_then(|coordinates| coordinates.in_direction(horizontal))
+            }
         }
     }
 
@@ -40,6 +42,84 @@ impl Coordinates {
             },
         )
     }
+
+    pub fn manhattan_distance(&self, to: Coordinates) -> usize {
+        self.0.abs_diff(to.0) + self.1.abs_diff(to.1)
+    }
+
+    pub fn chebyshev_distance(&self, to: Coordinates) -> usize {
+        self.0.abs_diff(to.0).max(self.1.abs_diff(to.1))
+    }
+
+    pub fn distance(&self, to: Coordinates, metric: Metric) -> usize {
+        match metric {
+            Metric::Manhattan => self.manhattan_distance(to),
+            Metric::Chebyshev => self.chebyshev_distance(to),
+        }
+    }
+
+    // Like direction_to but can also point diagonally when using Connectivity::Eight
+    pub fn direction_to_with(&self, to: Coordinates, connectivity: Connectivity) -> Option<Direction> {
+        match connectivity {
+            Connectivity::Four => self.direction_to(to),
+            Connectivity::Eight => {
+                let dx = to.0 as i64 - self.0 as i64;
+                let dy = to.1 as i64 - self.1 as i64;
+
+                let vertical = if dy < 0 { Some(Direction::Up) } else if dy > 0 { Some(Direction::Down) } else { None };
+                let horizontal = if dx < 0 { Some(Direction::Left) } else if dx > 0 { Some(Direction::Right) } else { None };
+
+                // Go straight if target is more than twice as far along one axis than the other
+                match (vertical, horizontal) {
+                    (None, None) => None,
+                    (Some(vertical), None) => Some(vertical),
+                    (None, Some(horizontal)) => Some(horizontal),
+                    (Some(vertical), Some(_)) if 2 * dx.abs() < dy.abs() => Some(vertical),
+                    (Some(_), Some(horizontal)) if 2 * dy.abs() < dx.abs() => Some(horizontal),
+                    (Some(vertical), Some(horizontal)) => Direction::diagonal(vertical, horizontal),
+                }
+            }
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy)]
+pub enum Metric {
+    Manhattan,
+    Chebyshev,
+}
+
+// Movement ruleset: Four allows only orthogonal moves, Eight allows diagonal moves as well as long
+// as they do not cut a corner of a tile that is not walkable
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy, Default)]
+pub enum Connectivity {
+    #[default]
+    Four,
+    Eight,
+}
+
+impl Connectivity {
+    pub fn directions(&self) -> DirectionClockwiseIter {
+        DirectionClockwiseIter {
+            direction: Some(Direction::Up),
+            connectivity: *self,
+        }
+    }
+
+    // Distance metric equal to number of steps on empty terrain
+    pub fn metric(&self) -> Metric {
+        match *self {
+            Connectivity::Four => Metric::Manhattan,
+            Connectivity::Eight => Metric::Chebyshev,
+        }
+    }
+
+    pub fn allows(&self, direction: Direction) -> bool {
+        match *self {
+            Connectivity::Four => !direction.is_diagonal(),
+            Connectivity::Eight => true,
+        }
+    }
 }
 
 #[derive(Debug, PartialEq, Eq, Hash, Clone, Copy)]
@@ -48,12 +128,39 @@ pub enum Direction {
     Right,
     Down,
     Left,
+    UpRight,
+    DownRight,
+    DownLeft,
+    UpLeft,
 }
 
 impl Direction {
     fn clockwise() -> DirectionClockwiseIter {
-        DirectionClockwiseIter {
-            direction: Some(Direction::Up),
+        Connectivity::Four.directions()
+    }
+
+    fn diagonal(vertical: Direction, horizontal: Direction) -> Option<Direction> {
+        match (vertical, horizontal) {
+            (Direction::Up, Direction::Right) => Some(Direction::UpRight),
+            (Direction::Down, Direction::Right) => Some(Direction::DownRight),
+            (Direction::Down, Direction::Left) => Some(Direction::DownLeft),
+            (Direction::Up, Direction::Left) => Some(Direction::UpLeft),
+            _ => None,
+        }
+    }
+
+    pub fn is_diagonal(&self) -> bool {
+        self.components().is_some()
+    }
+
+    // Vertical and horizontal direction making up a diagonal direction
+    pub fn components(&self) -> Option<(Direction, Direction)> {
+        match *self {
+            Direction::UpRight => Some((Direction::Up, Direction::Right)),
+            Direction::DownRight => Some((Direction::Down, Direction::Right)),
+            Direction::DownLeft => Some((Direction::Down, Direction::Left)),
+            Direction::UpLeft => Some((Direction::Up, Direction::Left)),
+            _ => None,
         }
     }
 }
@@ -61,18 +168,25 @@ impl Direction {
 #[derive(Debug)]
 pub struct DirectionClockwiseIter {
     direction: Option<Direction>,
+    connectivity: Connectivity,
 }
 
 impl Iterator for DirectionClockwiseIter {
     type Item = Direction;
 
     fn next(&mut self) -> Option<Direction> {
-        let next_direction = match self.direction {
-            Some(Direction::Up) => Some(Direction::Right),
-            Some(Direction::Right) => Some(Direction::Down),
-            Some(Direction::Down) => Some(Direction::Left),
-            Some(Direction::Left) => None,
-            None => None,
+        let next_direction = match (self.connectivity, self.direction) {
+            (Connectivity::Four, Some(Direction::Up)) => Some(Direction::Right),
+            (Connectivity::Four, Some(Direction::Right)) => Some(Direction::Down),
+            (Connectivity::Four, Some(Direction::Down)) => Some(Direction::Left),
+            (Connectivity::Eight, Some(Direction::Up)) => Some(Direction::UpRight),
+            (Connectivity::Eight, Some(Direction::UpRight)) => Some(Direction::Right),
+            (Connectivity::Eight, Some(Direction::Right)) => Some(Direction::DownRight),
+            (Connectivity::Eight, Some(Direction::DownRight)) => Some(Direction::Down),
+            (Connectivity::Eight, Some(Direction::Down)) => Some(Direction::DownLeft),
+            (Connectivity::Eight, Some(Direction::DownLeft)) => Some(Direction::Left),
+            (Connectivity::Eight, Some(Direction::Left)) => Some(Direction::UpLeft),
+            _ => None,
         };
         let ret = self.direction;
         self.direction = next_direction;
@@ -80,16 +194,25 @@ impl Iterator for DirectionClockwiseIter {
     }
 }
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Clone, Copy)]
 pub struct Location<'t> {
     terrain: &'t Terrain,
+    index: usize,
     pub coordinates: Coordinates,
     pub tile: &'t Tile,
 }
 
+impl<'t> fmt::Debug for Location<'t> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Location({}, {})[{:?}]", self.coordinates.0, self.coordinates.1, self.tile)
+    }
+}
+
+// Location identity is its tile index within terrain with given fingerprint so that locations
+// obtained from cloned or rebuilt terrain compare equal and can be kept between game rounds
 impl<'t> PartialEq for Location<'t> {
     fn eq(&self, other: &Location<'t>) -> bool {
-        ptr::eq(self.tile, other.tile)
+        self.index == other.index && self.terrain.fingerprint == other.terrain.fingerprint
     }
 }
 
@@ -97,18 +220,39 @@ impl<'t> Eq for Location<'t> {}
 
 impl<'t> Hash for Location<'t> {
     fn hash<H: Hasher>(&self, state: &mut H) {
-        state.write_usize((self.tile as *const Tile) as usize)
+        state.write_usize(self.index)
     }
 }
 
 impl<'t> Location<'t> {
+    // Index of the tile in row-major order
+    pub fn index(&self) -> usize {
+        self.index
+    }
+
     pub fn neighbours(&self) -> NeighboursIter<'t> {
         NeighboursIter {
-            location: self.clone(),
+            location: *self,
             directions: Direction::clockwise(),
         }
     }
 
+    pub fn neighbours_with(&self, connectivity: Connectivity) -> NeighboursIter<'t> {
+        NeighboursIter {
+            location: *self,
+            directions: connectivity.directions(),
+        }
+    }
+
+    // Diagonal step cuts a corner if any of the two tiles it passes between is not walkable
+    pub fn cuts_corner(&self, direction: Direction) -> bool {
+        direction.components().map(|(vertical, horizontal)| {
+            [vertical, horizontal].iter().any(|&direction| {
+                self.in_direction(direction).map(|location| !location.walkable()).unwrap_or(true)
+            })
+        }).unwrap_or(false)
+    }
+
     pub fn in_direction(&self, direction: Direction) -> Option<Location<'t>> {
         self.coordinates
             .in_direction(direction)
@@ -119,11 +263,21 @@ impl<'t> Location<'t> {
         self.coordinates.direction_to(to.coordinates)
     }
 
+    pub fn direction_to_with(&self, to: Location<'t>, connectivity: Connectivity) -> Option<Direction> {
+        self.coordinates.direction_to_with(to.coordinates, connectivity)
+    }
+
     pub fn walkable(&self) -> bool {
-        match *self.tile {
-            Tile::Empty => true,
-            _ => false,
-        }
+        self.tile.walkable()
+    }
+
+    pub fn buildable(&self) -> bool {
+        self.tile.buildable()
+    }
+
+    // Number of cycles it takes to enter this location; None if it cannot be entered
+    pub fn movement_cost(&self) -> Option<u64> {
+        self.tile.movement_cost()
     }
 }
 
@@ -136,7 +290,7 @@ impl<'t> Iterator for NeighboursIter<'t> {
     type Item = (Direction, Location<'t>);
 
     fn next(&mut self) -> Option<(Direction, Location<'t>)> {
-        while let Some(direction) = self.directions.next() {
+        for direction in self.directions.by_ref() {
             if let ret @ Some(_) = self.location
                 .in_direction(direction)
                 .map(|location| (direction, location))
@@ -152,11 +306,51 @@ impl<'t> Iterator for NeighboursIter<'t> {
 pub enum Tile {
     Empty,
     Wall,
+    Rough,
+    Water,
+    Bridge,
 }
 
-#[derive(Debug)]
+impl Tile {
+    pub fn movement_cost(&self) -> Option<u64> {
+        match *self {
+            Tile::Empty | Tile::Bridge => Some(1),
+            Tile::Rough => Some(2),
+            Tile::Wall | Tile::Water => None,
+        }
+    }
+
+    pub fn walkable(&self) -> bool {
+        self.movement_cost().is_some()
+    }
+
+    // Can buildings and resources be placed on this tile; bridges can only be crossed by units
+    pub fn buildable(&self) -> bool {
+        self.walkable() && *self != Tile::Bridge
+    }
+
+    // Can units see across this tile; water is impassable but does not block sight
+    pub fn transparent(&self) -> bool {
+        *self != Tile::Wall
+    }
+}
+
+// Tiles are stored in single row-major buffer; index of a tile is y * width + x
+fn tile_index(width: usize, height: usize, coordinates: Coordinates) -> Option<usize> {
+    if coordinates.0 < width && coordinates.1 < height {
+        Some(coordinates.1 * width + coordinates.0)
+    } else {
+        None
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub struct Terrain {
-    tiles: Vec<Vec<Tile>>, // TODO: Matix
+    width: usize,
+    height: usize,
+    tiles: Vec<Tile>,
+    // Hash of the tiles computed once when terrain is built
+    fingerprint: u64,
 }
 
 pub struct Dimension(usize);
@@ -177,36 +371,112 @@ impl Dimension {
 
 impl Terrain {
     pub fn width(&self) -> usize {
-        self.tiles.iter().next().unwrap().len()
+        self.width
     }
 
     pub fn height(&self) -> usize {
+        self.height
+    }
+
+    pub fn size(&self) -> usize {
         self.tiles.len()
     }
 
-    pub fn location(&self, coordinates: Coordinates) -> Option<Location> {
-        self.tiles
-            .get(coordinates.1)
-            .and_then(|row| row.get(coordinates.0))
-            .map(|tile| {
-                Location {
-                    terrain: &self,
-                    coordinates: coordinates,
-                    tile: tile,
-                }
-            })
+    // Same for clones and terrains built from the same tiles; cheap way for caches to tell if
+    // they are used with different terrain
+    pub fn fingerprint(&self) -> u64 {
+        self.fingerprint
     }
 
-    pub fn rows(&self) -> RowIter {
-        fn to_row<'t>((row_no, (tiles, terrain)): (usize, (&'t Vec<Tile>, &'t Terrain))) -> Row<'t> {
-            Row {
-                terrain: terrain,
-                row_no: row_no,
-                tiles: tiles.as_slice(),
+    pub fn index(&self, coordinates: Coordinates) -> Option<usize> {
+        tile_index(self.width, self.height, coordinates)
+    }
+
+    pub fn coordinates(&self, index: usize) -> Option<Coordinates> {
+        if index < self.tiles.len() {
+            Some(Coordinates(index % self.width, index / self.width))
+        } else {
+            None
+        }
+    }
+
+    pub fn location(&self, coordinates: Coordinates) -> Option<Location<'_>> {
+        self.index(coordinates).map(|index| {
+            Location {
+                terrain: self,
+                index,
+                coordinates,
+                tile: &self.tiles[index],
+            }
+        })
+    }
+
+    pub fn location_by_index(&self, index: usize) -> Option<Location<'_>> {
+        self.coordinates(index).map(|coordinates| {
+            Location {
+                terrain: self,
+                index,
+                coordinates,
+                tile: &self.tiles[index],
+            }
+        })
+    }
+
+    // All locations in rectangle with given corners (inclusive) that are within the map
+    pub fn rectangle(&self, top_left: Coordinates, bottom_right: Coordinates) -> AreaIter<'_> {
+        AreaIter::new(self, Shape::Rectangle(top_left, bottom_right))
+    }
+
+    // Locations within given distance from center; diamond for Manhattan and square for Chebyshev
+    pub fn within(&self, center: Coordinates, radius: usize, metric: Metric) -> AreaIter<'_> {
+        AreaIter::new(self, Shape::Within(center, metric, radius))
+    }
+
+    pub fn diamond(&self, center: Coordinates, radius: usize) -> AreaIter<'_> {
+        self.within(center, radius, Metric::Manhattan)
+    }
+
+    // Locations at exactly given distance from center
+    pub fn ring(&self, center: Coordinates, radius: usize, metric: Metric) -> AreaIter<'_> {
+        AreaIter::new(self, Shape::Ring(center, metric, radius))
+    }
+
+    // No tile on the straight line between from and to (both excluded) blocks sight
+    pub fn line_of_sight(&self, from: Coordinates, to: Coordinates) -> bool {
+        let (x1, y1) = (to.0 as i64, to.1 as i64);
+        let (dx, dy) = ((x1 - from.0 as i64).abs(), -(y1 - from.1 as i64).abs());
+        let (step_x, step_y) = (if from.0 < to.0 { 1 } else { -1 }, if from.1 < to.1 { 1 } else { -1 });
+
+        // Bresenham's line
+        let (mut x, mut y) = (from.0 as i64, from.1 as i64);
+        let mut error = dx + dy;
+        while (x, y) != (x1, y1) {
+            let doubled = 2 * error;
+            if doubled >= dy {
+                error += dy;
+                x += step_x;
+            }
+            if doubled <= dx {
+                error += dx;
+                y += step_y;
+            }
+            if (x, y) != (x1, y1) {
+                let transparent = self.location(Coordinates(x as usize, y as usize))
+                    .map(|location| location.tile.transparent())
+                    .unwrap_or(false);
+                if !transparent {
+                    return false;
+                }
             }
         }
-        let terrains = Some(self).into_iter().cycle();
-        RowIter(self.tiles.iter().zip(terrains).enumerate().map(to_row))
+        true
+    }
+
+    pub fn rows(&self) -> RowIter<'_> {
+        RowIter {
+            terrain: self,
+            rows: self.tiles.chunks(self.width).enumerate(),
+        }
     }
 }
 
@@ -218,7 +488,9 @@ pub enum TerrainBuilderError {
 }
 
 pub struct TerrainBuilder {
-    tiles: Vec<Vec<Tile>>, // TODO: Matix
+    width: usize,
+    height: usize,
+    tiles: Vec<Tile>,
 }
 
 impl TerrainBuilder {
@@ -227,32 +499,41 @@ impl TerrainBuilder {
         let height = height.unwrap();
 
         TerrainBuilder {
-            tiles: (0..height)
-                .map(|_row| (0..width).map(|_col| Tile::Empty).collect())
-                .collect(),
+            width,
+            height,
+            tiles: vec![Tile::Empty; width * height],
         }
     }
 
     pub fn place(mut self, coordinates: Coordinates, tile: Tile) -> Result<TerrainBuilder, TerrainBuilderError> {
-        self.tiles
-            .get_mut(coordinates.1)
-            .and_then(|row| row.get_mut(coordinates.0))
-            .ok_or(TerrainBuilderError::OutOfTerrain(coordinates))
-            .and_then(|terrain_tile| if *terrain_tile != Tile::Empty {
-                Err(TerrainBuilderError::CoordinatesAlreadyOccupied(
+        let index = tile_index(self.width, self.height, coordinates)
+            .ok_or(TerrainBuilderError::OutOfTerrain(coordinates))?;
+
+        {
+            let terrain_tile = &mut self.tiles[index];
+            if *terrain_tile != Tile::Empty {
+                return Err(TerrainBuilderError::CoordinatesAlreadyOccupied(
                     coordinates,
                     terrain_tile.clone(),
-                ))
-            } else {
-                Ok(terrain_tile)
-            })
-            .map(|terrain_tile| *terrain_tile = tile)
-            .map(|_| self)
+                ));
+            }
+            *terrain_tile = tile;
+        }
+
+        Ok(self)
     }
 
     pub fn build(self) -> Terrain {
+        let mut hasher = DefaultHasher::new();
+        self.width.hash(&mut hasher);
+        self.height.hash(&mut hasher);
+        self.tiles.hash(&mut hasher);
+
         Terrain {
-            tiles: self.tiles
+            width: self.width,
+            height: self.height,
+            tiles: self.tiles,
+            fingerprint: hasher.finish(),
         }
     }
 
@@ -265,7 +546,114 @@ impl TerrainBuilder {
             .build()
     }
 }
-    
+
+#[derive(Debug, Clone, Copy)]
+enum Shape {
+    // Corners (inclusive)
+    Rectangle(Coordinates, Coordinates),
+    // Center, metric and largest distance from center
+    Within(Coordinates, Metric, usize),
+    // Center, metric and exact distance from center
+    Ring(Coordinates, Metric, usize),
+}
+
+impl Shape {
+    // First and last row (inclusive)
+    fn rows(&self) -> (usize, usize) {
+        match *self {
+            Shape::Rectangle(top_left, bottom_right) => (top_left.1, bottom_right.1),
+            Shape::Within(center, _, radius) | Shape::Ring(center, _, radius) => {
+                (center.1.saturating_sub(radius), center.1.saturating_add(radius))
+            }
+        }
+    }
+
+    // Column ranges (inclusive, may reach out of map) the shape covers in given row, left to right
+    fn spans(&self, row: usize) -> [Option<(i64, i64)>; 2] {
+        let (center, metric, radius, ring) = match *self {
+            Shape::Rectangle(top_left, bottom_right) => {
+                return [Some((top_left.0 as i64, bottom_right.0 as i64)), None];
+            }
+            Shape::Within(center, metric, radius) => (center, metric, radius as i64, false),
+            Shape::Ring(center, metric, radius) => (center, metric, radius as i64, true),
+        };
+        let x = center.0 as i64;
+        let dy = (row as i64 - center.1 as i64).abs();
+        let half = match metric {
+            Metric::Manhattan => radius - dy,
+            Metric::Chebyshev => radius,
+        };
+
+        // Ring has only its ends in a row unless the row is its top or bottom side
+        if !ring || half == 0 || (metric == Metric::Chebyshev && dy == radius) {
+            [Some((x - half, x + half)), None]
+        } else {
+            [Some((x - half, x - half)), Some((x + half, x + half))]
+        }
+    }
+}
+
+// Iterates locations of a rectangle, diamond, square or their outlines clipped to terrain, row by
+// row; only the locations of the shape are visited
+pub struct AreaIter<'t> {
+    terrain: &'t Terrain,
+    shape: Shape,
+    row: usize,
+    last_row: usize,
+    // Remaining columns of current row
+    spans: [Option<(usize, usize)>; 2],
+}
+
+impl<'t> AreaIter<'t> {
+    fn new(terrain: &'t Terrain, shape: Shape) -> AreaIter<'t> {
+        let (first_row, last_row) = shape.rows();
+        let mut area = AreaIter {
+            terrain,
+            shape,
+            row: first_row,
+            last_row: last_row.min(terrain.height - 1),
+            spans: [None, None],
+        };
+        if first_row <= area.last_row {
+            area.start_row(first_row);
+        }
+        area
+    }
+
+    fn start_row(&mut self, row: usize) {
+        self.row = row;
+        let last_column = self.terrain.width as i64 - 1;
+        let shape_spans = self.shape.spans(row);
+        for (span, shape_span) in self.spans.iter_mut().zip(shape_spans.iter()) {
+            *span = shape_span
+                .map(|(first, last)| (first.max(0), last.min(last_column)))
+                .filter(|&(first, last)| first <= last)
+                .map(|(first, last)| (first as usize, last as usize));
+        }
+    }
+}
+
+impl<'t> Iterator for AreaIter<'t> {
+    type Item = Location<'t>;
+
+    fn next(&mut self) -> Option<Location<'t>> {
+        loop {
+            for span in &mut self.spans {
+                if let Some((column, last)) = *span {
+                    *span = if column < last { Some((column + 1, last)) } else { None };
+                    return self.terrain.location(Coordinates(column, self.row));
+                }
+            }
+
+            if self.row >= self.last_row {
+                return None;
+            }
+            let row = self.row + 1;
+            self.start_row(row);
+        }
+    }
+}
+
 pub struct Row<'t> {
     terrain: &'t Terrain,
     pub row_no: usize,
@@ -277,40 +665,52 @@ impl<'t> IntoIterator for Row<'t> {
     type IntoIter = RowLocationsIter<'t>;
 
     fn into_iter(self) -> RowLocationsIter<'t> {
-        let terrains = Some(self.terrain).into_iter().cycle();
         RowLocationsIter {
+            terrain: self.terrain,
             row_no: self.row_no,
-            row_iter: self.tiles.iter().zip(terrains).enumerate(),
+            row_iter: self.tiles.iter().enumerate(),
         }
     }
 }
 
-type ESIter<'i, T> = Enumerate<ZipIter<SliceIter<'i, T>, Cycle<OptionIntoIter<&'i Terrain>>>>;
-type ToRowFn<'t> = fn((usize, (&'t Vec<Tile>, &'t Terrain))) -> Row<'t>;
-pub struct RowIter<'t>(MapIter<ESIter<'t, Vec<Tile>>, ToRowFn<'t>>);
+pub struct RowIter<'t> {
+    terrain: &'t Terrain,
+    rows: Enumerate<Chunks<'t, Tile>>,
+}
 
 impl<'t> Iterator for RowIter<'t> {
     type Item = Row<'t>;
 
     fn next(&mut self) -> Option<Row<'t>> {
-        self.0.next()
+        let terrain = self.terrain;
+        self.rows.next().map(|(row_no, tiles)| {
+            Row {
+                terrain,
+                row_no,
+                tiles,
+            }
+        })
     }
 }
 
 pub struct RowLocationsIter<'t> {
+    terrain: &'t Terrain,
     row_no: usize,
-    row_iter: ESIter<'t, Tile>,
+    row_iter: Enumerate<SliceIter<'t, Tile>>,
 }
 
 impl<'t> Iterator for RowLocationsIter<'t> {
     type Item = Location<'t>;
 
     fn next(&mut self) -> Option<Location<'t>> {
-        self.row_iter.next().map(|(col_no, (tile, terrain))| {
+        let terrain = self.terrain;
+        let row_no = self.row_no;
+        self.row_iter.next().map(|(col_no, tile)| {
             Location {
-                terrain: terrain,
-                coordinates: Coordinates(col_no, self.row_no),
-                tile: tile,
+                terrain,
+                index: row_no * terrain.width + col_no,
+                coordinates: Coordinates(col_no, row_no),
+                tile,
             }
         })
     }
@@ -333,22 +733,22 @@ mod tests {
         assert!(Coordinates(1, 1).in_direction(Direction::Right).is_some());
 
         assert!(
-            Coordinates(usize::max_value(), usize::max_value())
+            Coordinates(usize::MAX, usize::MAX)
                 .in_direction(Direction::Up)
                 .is_some()
         );
         assert!(
-            Coordinates(usize::max_value(), usize::max_value())
+            Coordinates(usize::MAX, usize::MAX)
                 .in_direction(Direction::Left)
                 .is_some()
         );
         assert!(
-            Coordinates(usize::max_value(), usize::max_value())
+            Coordinates(usize::MAX, usize::MAX)
                 .in_direction(Direction::Down)
                 .is_none()
         );
         assert!(
-            Coordinates(usize::max_value(), usize::max_value())
+            Coordinates(usize::MAX, usize::MAX)
                 .in_direction(Direction::Right)
                 .is_none()
         );
@@ -420,6 +820,168 @@ mod tests {
         )
     }
 
+    #[test]
+    fn terrain_index_coordinates() {
+        let terrain = TerrainBuilder::new(Dimension::new(3).unwrap(), Dimension::new(2).unwrap()).build();
+
+        assert_eq!(terrain.size(), 6);
+        assert_eq!(terrain.index(Coordinates(0, 0)), Some(0));
+        assert_eq!(terrain.index(Coordinates(2, 0)), Some(2));
+        assert_eq!(terrain.index(Coordinates(0, 1)), Some(3));
+        assert_eq!(terrain.index(Coordinates(3, 0)), None);
+        assert_eq!(terrain.index(Coordinates(0, 2)), None);
+
+        assert_eq!(terrain.coordinates(4), Some(Coordinates(1, 1)));
+        assert_eq!(terrain.coordinates(6), None);
+
+        assert_eq!(
+            terrain.rows()
+                .flat_map(|row| row.into_iter())
+                .map(|location| (location.index(), location.coordinates))
+                .collect::<Vec<_>>(),
+            (0..6).map(|index| (index, terrain.coordinates(index).unwrap())).collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn location_identity() {
+        let terrain = TerrainBuilder::terrain_8x8_wall1();
+        let other_terrain = terrain.clone();
+
+        assert_eq!(terrain, other_terrain);
+        assert_eq!(terrain.fingerprint(), TerrainBuilder::terrain_8x8_wall1().fingerprint());
+        let empty_terrain = TerrainBuilder::new(Dimension::new(8).unwrap(), Dimension::new(8).unwrap()).build();
+        assert!(terrain.fingerprint() != empty_terrain.fingerprint());
+        assert_eq!(
+            terrain.location(Coordinates(3, 4)).unwrap(),
+            other_terrain.location(Coordinates(3, 4)).unwrap()
+        );
+        assert_eq!(
+            terrain.location(Coordinates(3, 4)).unwrap(),
+            other_terrain.location_by_index(35).unwrap()
+        );
+        assert_ne!(
+            terrain.location(Coordinates(3, 4)).unwrap(),
+            other_terrain.location(Coordinates(4, 3)).unwrap()
+        );
+
+        // Same index in terrain of different size is a different location
+        let wide_terrain = TerrainBuilder::new(Dimension::new(16).unwrap(), Dimension::new(4).unwrap()).build();
+        assert_ne!(
+            terrain.location_by_index(35).unwrap(),
+            wide_terrain.location_by_index(35).unwrap()
+        );
+        // and so it is in terrain of the same size with different tiles
+        assert_ne!(
+            terrain.location_by_index(35).unwrap(),
+            empty_terrain.location_by_index(35).unwrap()
+        );
+    }
+
+    #[test]
+    fn line_of_sight() {
+        let terrain = TerrainBuilder::new(Dimension::new(5).unwrap(), Dimension::new(5).unwrap())
+            .place(Coordinates(2, 0), Tile::Wall).unwrap()
+            .place(Coordinates(2, 2), Tile::Water).unwrap()
+            .build();
+
+        assert!(!terrain.line_of_sight(Coordinates(0, 0), Coordinates(4, 0)));
+        assert!(terrain.line_of_sight(Coordinates(0, 2), Coordinates(4, 2)));
+        assert!(terrain.line_of_sight(Coordinates(0, 4), Coordinates(4, 0)));
+        // Blocking tiles at either end do not matter
+        assert!(terrain.line_of_sight(Coordinates(2, 0), Coordinates(2, 4)));
+        assert!(terrain.line_of_sight(Coordinates(4, 4), Coordinates(2, 0)));
+    }
+
+    #[test]
+    fn test_direction_to_with_eight() {
+        assert_eq!(Coordinates(1, 1).direction_to_with(Coordinates(1, 1), Connectivity::Eight), None);
+        assert_eq!(
+            Coordinates(1, 1).direction_to_with(Coordinates(2, 2), Connectivity::Eight),
+            Some(Direction::DownRight)
+        );
+        assert_eq!(
+            Coordinates(1, 1).direction_to_with(Coordinates(0, 0), Connectivity::Eight),
+            Some(Direction::UpLeft)
+        );
+        assert_eq!(
+            Coordinates(0, 4).direction_to_with(Coordinates(1, 0), Connectivity::Eight),
+            Some(Direction::Up)
+        );
+        assert_eq!(
+            Coordinates(0, 2).direction_to_with(Coordinates(4, 0), Connectivity::Eight),
+            Some(Direction::UpRight)
+        );
+        assert_eq!(
+            Coordinates(0, 0).direction_to_with(Coordinates(1, 2), Connectivity::Four),
+            Some(Direction::Down)
+        );
+    }
+
+    #[test]
+    fn location_cuts_corner() {
+        let terrain = TerrainBuilder::terrain_8x8_wall1();
+        let location = terrain.location(Coordinates(3, 3)).unwrap();
+
+        assert!(location.cuts_corner(Direction::UpRight));
+        assert!(location.cuts_corner(Direction::DownRight));
+        assert!(location.cuts_corner(Direction::DownLeft));
+        assert!(!location.cuts_corner(Direction::UpLeft));
+        assert!(!location.cuts_corner(Direction::Right));
+
+        assert_eq!(
+            location.neighbours_with(Connectivity::Eight).count(),
+            8
+        );
+    }
+
+    #[test]
+    fn distances() {
+        assert_eq!(Coordinates(1, 5).manhattan_distance(Coordinates(4, 1)), 7);
+        assert_eq!(Coordinates(1, 5).chebyshev_distance(Coordinates(4, 1)), 4);
+        assert_eq!(Coordinates(4, 1).distance(Coordinates(1, 5), Metric::Chebyshev), 4);
+    }
+
+    #[test]
+    fn terrain_areas() {
+        let terrain = TerrainBuilder::new(Dimension::new(8).unwrap(), Dimension::new(8).unwrap()).build();
+        let coordinates = |area: AreaIter| area.map(|location| location.coordinates).collect::<Vec<_>>();
+
+        assert_eq!(
+            coordinates(terrain.rectangle(Coordinates(6, 6), Coordinates(9, 9))),
+            vec![Coordinates(6, 6), Coordinates(7, 6), Coordinates(6, 7), Coordinates(7, 7)]
+        );
+        assert_eq!(
+            coordinates(terrain.diamond(Coordinates(0, 0), 1)),
+            vec![Coordinates(0, 0), Coordinates(1, 0), Coordinates(0, 1)]
+        );
+        assert_eq!(terrain.diamond(Coordinates(4, 4), 2).count(), 13);
+        assert_eq!(terrain.within(Coordinates(4, 4), 2, Metric::Chebyshev).count(), 25);
+        assert_eq!(terrain.ring(Coordinates(4, 4), 2, Metric::Chebyshev).count(), 16);
+        assert_eq!(
+            coordinates(terrain.ring(Coordinates(4, 4), 1, Metric::Manhattan)),
+            vec![Coordinates(4, 3), Coordinates(3, 4), Coordinates(5, 4), Coordinates(4, 5)]
+        );
+        assert_eq!(terrain.ring(Coordinates(20, 20), 1, Metric::Manhattan).count(), 0);
+
+        // Rings and filled areas visit the same locations as filtering the whole map would
+        let all = terrain.rectangle(Coordinates(0, 0), Coordinates(7, 7)).collect::<Vec<_>>();
+        for &center in &[Coordinates(0, 0), Coordinates(2, 5), Coordinates(7, 3)] {
+            for &metric in &[Metric::Manhattan, Metric::Chebyshev] {
+                for radius in 0..16 {
+                    let at = |min, max| {
+                        all.iter()
+                            .map(|location| location.coordinates)
+                            .filter(|coordinates| (min..=max).contains(&coordinates.distance(center, metric)))
+                            .collect::<Vec<_>>()
+                    };
+                    assert_eq!(coordinates(terrain.ring(center, radius, metric)), at(radius, radius));
+                    assert_eq!(coordinates(terrain.within(center, radius, metric)), at(0, radius));
+                }
+            }
+        }
+    }
+
     #[test]
     fn test_direction_iter() {
         assert_eq!(
@@ -430,6 +992,20 @@ mod tests {
                 Direction::Down,
                 Direction::Left,
             ]
+        );
+
+        assert_eq!(
+            Connectivity::Eight.directions().collect::<Vec<_>>(),
+            vec![
+                Direction::Up,
+                Direction::UpRight,
+                Direction::Right,
+                Direction::DownRight,
+                Direction::Down,
+                Direction::DownLeft,
+                Direction::Left,
+                Direction::UpLeft,
+            ]
         )
     }
 }
@@ -0,0 +1,43 @@
+use game::{Terrain, TerrainBuilder, Player, Colour, Coordinates, Location, Object, GameState,
+           GameStateBuilder};
+
+// Mario and Luigi on a terrain; the usual starting point of a test
+pub struct TwoPlayers {
+    pub terrain: Terrain,
+    pub p1: Player,
+    pub p2: Player,
+}
+
+impl Default for TwoPlayers {
+    // The 8x8 map with a diagonal wall
+    fn default() -> TwoPlayers {
+        TwoPlayers::on(TerrainBuilder::terrain_8x8_wall1())
+    }
+}
+
+impl TwoPlayers {
+    pub fn on(terrain: Terrain) -> TwoPlayers {
+        TwoPlayers {
+            terrain,
+            p1: Player::new("Mario", Colour::Red),
+            p2: Player::new("Luigi", Colour::Green),
+        }
+    }
+
+    pub fn location<'s>(&'s self, x: usize, y: usize) -> Location<'s> {
+        self.terrain.location(Coordinates(x, y)).expect("location out of terrain")
+    }
+
+    // Builder with all placements done; panics on placement that is not valid
+    pub fn builder<'s>(&'s self, placements: &[(Coordinates, Object<'s>)]) -> GameStateBuilder<'s, 's> {
+        let mut builder = GameStateBuilder::new("test", &self.terrain);
+        for &(coordinates, ref object) in placements {
+            builder.place(coordinates, object.clone()).expect("invalid placement");
+        }
+        builder
+    }
+
+    pub fn game<'s>(&'s self, placements: &[(Coordinates, Object<'s>)]) -> GameState<'s, 's> {
+        self.builder(placements).build_for_round(0)
+    }
+}
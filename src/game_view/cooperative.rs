@@ -0,0 +1,280 @@
+use std::collections::{HashMap, HashSet};
+
+use pathfinding;
+
+use game::{EntityID, Location, Direction, Connectivity, Order};
+use game_view::{GameView, DistanceField};
+
+// Space-time reservations: which entity is standing on a location (by index) at given cycle
+// counted from now
+#[derive(Debug, Default, Clone)]
+pub struct ReservationTable {
+    reservations: HashMap<(usize, u64), EntityID>,
+}
+
+impl ReservationTable {
+    pub fn new() -> ReservationTable {
+        Default::default()
+    }
+
+    pub fn reserve(&mut self, location: Location, cycle: u64, entity_id: EntityID) {
+        self.reservations.insert((location.index(), cycle), entity_id);
+    }
+
+    pub fn is_free_for(&self, location: Location, cycle: u64, entity_id: EntityID) -> bool {
+        self.is_index_free_for(location.index(), cycle, entity_id)
+    }
+
+    fn is_index_free_for(&self, index: usize, cycle: u64, entity_id: EntityID) -> bool {
+        self.reservations.get(&(index, cycle)).map(|&owner| owner == entity_id).unwrap_or(true)
+    }
+}
+
+// Paths planned jointly for group of units; path lists location of the unit at each of the
+// following cycles starting with the current one
+#[derive(Debug, Clone)]
+pub struct CooperativePlan<'m> {
+    connectivity: Connectivity,
+    paths: HashMap<EntityID, Vec<Location<'m>>>,
+}
+
+impl<'m> CooperativePlan<'m> {
+    pub fn path(&self, entity_id: EntityID) -> Option<&[Location<'m>]> {
+        self.paths.get(&entity_id).map(|path| path.as_slice())
+    }
+
+    // Direction the unit should move in this cycle; None if it should wait
+    pub fn next_direction(&self, entity_id: EntityID) -> Option<Direction> {
+        self.paths.get(&entity_id).and_then(|path| match (path.first(), path.get(1)) {
+            (Some(from), Some(to)) => from.direction_to_with(*to, self.connectivity),
+            _ => None,
+        })
+    }
+
+    // Move orders for this cycle ordered by entity ID
+    pub fn orders(&self) -> Vec<Order> {
+        let mut entity_ids = self.paths.keys().cloned().collect::<Vec<_>>();
+        entity_ids.sort_by_key(|entity_id| entity_id.0);
+
+        entity_ids.into_iter()
+            .filter_map(|entity_id| {
+                self.next_direction(entity_id).map(|direction| Order::Move(entity_id, direction))
+            })
+            .collect()
+    }
+}
+
+impl<'p: 's, 'm: 's, 's> GameView<'p, 'm, 's> {
+    // Plans paths for group of units towards their goals over next window cycles so that they do
+    // not get into each other's way (windowed cooperative A*). Units are planned in given order:
+    // earlier units get priority; if a unit cannot find any conflict free path it is moved to the
+    // front and planning starts over. Goal that cannot be walked on is approached to its
+    // neighbouring location. Entities outside of the group are treated as static obstacles.
+    //
+    // A unit may not enter a location at the cycle another unit leaves it as the outcome of
+    // such move would depend on the order in which the moves are applied.
+    pub fn plan_cooperative(&self, goals: &[(EntityID, Location<'m>)], window: u64) -> CooperativePlan<'m> {
+        let mut order = goals.iter()
+            .filter(|&&(entity_id, _)| self.entity(entity_id).is_some())
+            .cloned()
+            .collect::<Vec<_>>();
+
+        let mut attempts = order.len();
+        loop {
+            let (paths, failed) = self.plan_in_order(&order, window);
+            match failed {
+                Some(position) if attempts > 0 => {
+                    attempts -= 1;
+                    let goal = order.remove(position);
+                    order.insert(0, goal);
+                }
+                _ => {
+                    return CooperativePlan {
+                        connectivity: self.connectivity(),
+                        paths,
+                    }
+                }
+            }
+        }
+    }
+
+    // Returns paths and position of first unit that had to fall back to waiting in place
+    fn plan_in_order(
+        &self,
+        goals: &[(EntityID, Location<'m>)],
+        window: u64,
+    ) -> (HashMap<EntityID, Vec<Location<'m>>>, Option<usize>) {
+        let terrain = self.terrain();
+        let connectivity = self.connectivity();
+        let group = goals.iter().map(|&(entity_id, _)| entity_id).collect::<HashSet<_>>();
+        let is_obstacle = |location: Location<'m>| {
+            self.game.get_entity_by_location(location)
+                .map(|entity| !group.contains(&entity.id))
+                .unwrap_or(false)
+        };
+
+        let mut reservations = ReservationTable::new();
+        let starts = goals.iter()
+            .map(|&(entity_id, _)| (entity_id, self.entity(entity_id).expect("no entity").location))
+            .collect::<Vec<_>>();
+
+        // Units stay where they are until they get planned
+        for &(entity_id, start) in &starts {
+            reservations.reserve(start, 0, entity_id);
+            reservations.reserve(start, 1, entity_id);
+        }
+
+        let mut paths = HashMap::new();
+        let mut failed = None;
+
+        for (position, (&(entity_id, start), &(_, goal))) in starts.iter().zip(goals.iter()).enumerate() {
+            let arrivals = if goal.walkable() && !is_obstacle(goal) {
+                vec![goal]
+            } else {
+                goal.neighbours_with(connectivity)
+                    .map(|(_direction, location)| location)
+                    .filter(|&location| location.walkable() && !is_obstacle(location))
+                    .collect()
+            };
+            let arrival_indices = arrivals.iter().map(|location| location.index()).collect::<HashSet<_>>();
+            let field = DistanceField::with_obstacles(terrain, connectivity, &arrivals, is_obstacle);
+            let busy_for = self.busy_for(entity_id);
+
+            // Unit can stay at location from given cycle until the end of the window
+            let can_stay = |index: usize, cycle: u64| {
+                (cycle..window + 2).all(|cycle| reservations.is_index_free_for(index, cycle, entity_id))
+            };
+
+            // Unit that cannot reach its goal will only wait
+            let found = pathfinding::astar(
+                &(start.index(), 0),
+                |&(index, cycle)| {
+                    let mut successors = Vec::new();
+
+                    // Whatever the unit does it will be standing on this location next cycle or
+                    // leaving it
+                    if cycle >= window || !reservations.is_index_free_for(index, cycle + 1, entity_id) {
+                        return successors;
+                    }
+
+                    successors.push(((index, cycle + 1), 1));
+
+                    if cycle < busy_for {
+                        return successors;
+                    }
+
+                    let location = terrain.location_by_index(index).expect("index out of terrain");
+                    for (direction, neighbour) in location.neighbours_with(connectivity) {
+                        if location.cuts_corner(direction) || is_obstacle(neighbour) || field.distance(neighbour).is_none() {
+                            continue;
+                        }
+
+                        let cost = match neighbour.movement_cost() {
+                            Some(cost) => cost,
+                            None => continue,
+                        };
+
+                        if (cycle + 1..cycle + cost + 1).all(|cycle| reservations.is_free_for(neighbour, cycle, entity_id)) {
+                            successors.push(((neighbour.index(), cycle + cost), cost));
+                        }
+                    }
+
+                    successors
+                },
+                |&(index, _cycle)| {
+                    terrain.location_by_index(index)
+                        .and_then(|location| field.distance(location))
+                        .unwrap_or(0)
+                },
+                |&(index, cycle)| cycle >= window || (arrival_indices.contains(&index) && can_stay(index, cycle))
+            ).map(|(steps, _cost)| steps);
+
+            if found.is_none() && failed.is_none() {
+                failed = Some(position);
+            }
+
+            // Expand steps to location at every cycle
+            let steps = found.unwrap_or_else(|| vec![(start.index(), 0)]);
+            let mut path = Vec::new();
+            for &(index, cycle) in &steps {
+                let location = terrain.location_by_index(index).expect("index out of terrain");
+                while (path.len() as u64) <= cycle {
+                    path.push(location);
+                }
+            }
+
+            for (cycle, &location) in path.iter().enumerate() {
+                reservations.reserve(location, cycle as u64, entity_id);
+                reservations.reserve(location, cycle as u64 + 1, entity_id);
+            }
+            let last = *path.last().expect("empty path");
+            for cycle in path.len() as u64..window + 2 {
+                reservations.reserve(last, cycle, entity_id);
+            }
+
+            paths.insert(entity_id, path);
+        }
+
+        (paths, failed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use game::{Coordinates, Object, Resource, Unit, Owned};
+    use game::test_support::TwoPlayers;
+
+    #[test]
+    fn cooperative_paths_do_not_collide() {
+        let players = TwoPlayers::default();
+        let p1 = &players.p1;
+
+        let mut game = players.game(&[
+            (Coordinates(4, 5), Object::Resources(Resource(10))),
+            (Coordinates(4, 7), Object::Unit(p1, Unit::Worker)),
+            (Coordinates(5, 7), Object::Unit(p1, Unit::Worker)),
+            (Coordinates(6, 7), Object::Unit(p1, Unit::Worker)),
+            (Coordinates(7, 7), Object::Unit(p1, Unit::Worker)),
+        ]);
+        let resource = players.location(4, 5);
+
+        // all workers head to the same resource
+        let mut goals = game.entities()
+            .filter(|&(_, entity)| entity.location != resource)
+            .map(|(entity_id, _)| (entity_id, resource))
+            .collect::<Vec<_>>();
+        goals.sort_by_key(|&(EntityID(id), _)| id);
+
+        for _cycle in 0..10 {
+            let orders = {
+                let view = game.view_for(p1);
+                let plan = view.plan_cooperative(&goals, 8);
+
+                for cycle in 0..8 {
+                    let mut taken = Vec::new();
+                    for &(entity_id, _) in &goals {
+                        let path = plan.path(entity_id).unwrap();
+                        let location = path.get(cycle).or(path.last()).unwrap();
+                        assert!(!taken.contains(location));
+                        taken.push(*location);
+                    }
+                }
+
+                let orders = plan.orders();
+                let mut sorted = orders.clone();
+                sorted.sort_by_key(|order| order.entity_id().0);
+                assert_eq!(orders, sorted);
+                orders
+            };
+            assert!(game.apply(orders.into_iter().map(|order| Owned(p1, order))).is_empty());
+        }
+
+        let next_to_resource = goals.iter()
+            .filter(|&&(entity_id, _)| {
+                game.get_entity(entity_id).unwrap().location.coordinates.manhattan_distance(resource.coordinates) == 1
+            })
+            .count();
+        assert_eq!(next_to_resource, 4);
+    }
+}
@@ -0,0 +1,160 @@
+use std::collections::{BinaryHeap, HashMap};
+use std::cmp::Reverse;
+
+use game::{Terrain, Location, Direction, Connectivity};
+
+// Cost of getting from every tile to the nearest of source locations, computed by uniform cost
+// search (BFS weighted by tile movement cost) over walkable tiles. Units can walk towards sources
+// by stepping downhill on the field.
+#[derive(Debug, Clone)]
+pub struct DistanceField {
+    connectivity: Connectivity,
+    distances: Vec<Option<u64>>,
+}
+
+impl DistanceField {
+    // Field over terrain only, ignoring entities
+    pub fn new(terrain: &Terrain, connectivity: Connectivity, sources: &[Location]) -> DistanceField {
+        DistanceField::with_obstacles(terrain, connectivity, sources, |_location| false)
+    }
+
+    // Field that cannot pass through locations for which is_obstacle returns true; sources are
+    // never treated as obstacles
+    pub fn with_obstacles<'t, F>(
+        terrain: &'t Terrain,
+        connectivity: Connectivity,
+        sources: &[Location<'t>],
+        is_obstacle: F,
+    ) -> DistanceField
+    where
+        F: Fn(Location<'t>) -> bool,
+    {
+        let mut distances = vec![None; terrain.size()];
+        let mut queue = BinaryHeap::new();
+
+        for source in sources {
+            distances[source.index()] = Some(0);
+            queue.push(Reverse((0, source.index())));
+        }
+
+        while let Some(Reverse((distance, index))) = queue.pop() {
+            if distances[index].map(|best| best < distance).unwrap_or(false) {
+                continue;
+            }
+
+            let location = terrain.location_by_index(index).expect("index out of terrain");
+            // Moving from neighbour to this location costs as much as entering this location
+            let cost = location.movement_cost().unwrap_or(1);
+
+            for (direction, neighbour) in location.neighbours_with(connectivity) {
+                if location.cuts_corner(direction) || is_obstacle(neighbour) {
+                    continue;
+                }
+
+                if neighbour.walkable() {
+                    let neighbour_distance = distance + cost;
+                    if distances[neighbour.index()].map(|best| neighbour_distance < best).unwrap_or(true) {
+                        distances[neighbour.index()] = Some(neighbour_distance);
+                        queue.push(Reverse((neighbour_distance, neighbour.index())));
+                    }
+                }
+            }
+        }
+
+        DistanceField {
+            connectivity,
+            distances,
+        }
+    }
+
+    // None if no source can be reached from this location
+    pub fn distance(&self, location: Location) -> Option<u64> {
+        self.distances.get(location.index()).and_then(|distance| *distance)
+    }
+
+    // Neighbouring location closest to the sources if it is closer than location itself
+    pub fn downhill<'t>(&self, location: Location<'t>) -> Option<(Direction, Location<'t>)> {
+        let current = self.distance(location);
+
+        location.neighbours_with(self.connectivity)
+            .filter(|&(direction, _)| !location.cuts_corner(direction))
+            .filter_map(|(direction, neighbour)| {
+                self.distance(neighbour).map(|distance| (distance, direction, neighbour))
+            })
+            .filter(|&(distance, _, _)| current.map(|current| distance < current).unwrap_or(true))
+            .min_by_key(|&(distance, _, _)| distance)
+            .map(|(_, direction, neighbour)| (direction, neighbour))
+    }
+}
+
+// Keeps distance fields for static sources (e.g. bases and resources) so they are computed once per
+// terrain; fields are dropped when cache is used with different terrain
+#[derive(Debug, Default, Clone)]
+pub struct DistanceFieldCache {
+    // Fingerprint of the terrain fields were computed on
+    terrain: Option<u64>,
+    fields: HashMap<(Connectivity, Vec<usize>), DistanceField>,
+}
+
+impl DistanceFieldCache {
+    pub fn new() -> DistanceFieldCache {
+        Default::default()
+    }
+
+    pub fn get(&mut self, terrain: &Terrain, connectivity: Connectivity, sources: &[Location]) -> &DistanceField {
+        if self.terrain != Some(terrain.fingerprint()) {
+            self.fields.clear();
+            self.terrain = Some(terrain.fingerprint());
+        }
+
+        let mut key = sources.iter().map(|location| location.index()).collect::<Vec<_>>();
+        key.sort();
+        key.dedup();
+
+        self.fields
+            .entry((connectivity, key))
+            .or_insert_with(|| DistanceField::new(terrain, connectivity, sources))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use game::{TerrainBuilder, Dimension, Coordinates, Tile, Object, Resource, Unit};
+    use game::test_support::TwoPlayers;
+
+    #[test]
+    fn distance_fields() {
+        let players = TwoPlayers::default();
+        let p1 = &players.p1;
+
+        let game = players.game(&[
+            (Coordinates(0, 7), Object::Resources(Resource(10))),
+            (Coordinates(1, 6), Object::Unit(p1, Unit::Worker)),
+        ]);
+        let view = game.view_for(p1);
+        let location = |x, y| players.location(x, y);
+
+        let mut cache = DistanceFieldCache::new();
+        let field = view.static_distance_field(&mut cache, &[location(0, 7)]).clone();
+        assert_eq!(field.distance(location(0, 7)), Some(0));
+        assert_eq!(field.distance(location(1, 6)), Some(2));
+        assert_eq!(field.distance(location(3, 4)), None);
+        assert_eq!(field.distance(location(3, 5)), Some(5));
+        assert_eq!(field.downhill(location(0, 5)), Some((Direction::Down, location(0, 6))));
+
+        // worker blocks the way in dynamic field
+        let field = view.distance_field(&[location(0, 7)]);
+        assert_eq!(field.distance(location(1, 6)), None);
+        assert_eq!(field.distance(location(2, 6)), Some(3));
+
+        // costs are of moving towards the sources
+        let terrain = TerrainBuilder::new(Dimension::new(3).unwrap(), Dimension::new(1).unwrap())
+            .place(Coordinates(1, 0), Tile::Rough).unwrap()
+            .build();
+        let location = |x| terrain.location(Coordinates(x, 0)).unwrap();
+        let field = DistanceField::new(&terrain, Connectivity::Four, &[location(0)]);
+        assert_eq!(field.distance(location(1)), Some(1));
+        assert_eq!(field.distance(location(2)), Some(3));
+    }
+}
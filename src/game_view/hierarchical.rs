@@ -0,0 +1,429 @@
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, HashMap};
+use std::slice;
+
+use pathfinding;
+
+use game::{Terrain, Location, Connectivity, Coordinates};
+use game_view::Navigator;
+
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy)]
+enum AbstractNode {
+    Tile(usize),
+    Goal,
+}
+
+// Abstraction of terrain for hierarchical path finding (HPA*): terrain is split into square clusters
+// and walkable openings between neighbouring clusters are represented by entrance tiles on both
+// sides. Entrances are connected by edges across cluster borders and by edges within a cluster
+// with cost of the cheapest path that does not leave it. Long paths are found over this small
+// graph and then refined to tile level steps one segment at a time.
+//
+// The abstraction only depends on terrain so it is meant to be built once per map and kept by
+// the AI.
+#[derive(Debug, Clone)]
+pub struct HierarchicalMap {
+    terrain: Terrain,
+    connectivity: Connectivity,
+    cluster_size: usize,
+    edges: HashMap<usize, Vec<(usize, u64)>>,
+    // Entrance tiles of each cluster
+    entrances: HashMap<usize, Vec<usize>>,
+}
+
+// Openings at least this long get entrance on both ends instead of single one in the middle
+const LONG_ENTRANCE: usize = 6;
+
+impl HierarchicalMap {
+    pub fn new(terrain: &Terrain, connectivity: Connectivity, cluster_size: usize) -> HierarchicalMap {
+        assert!(cluster_size > 0, "cluster size must be positive");
+
+        let mut map = HierarchicalMap {
+            terrain: terrain.clone(),
+            connectivity,
+            cluster_size,
+            edges: HashMap::new(),
+            entrances: HashMap::new(),
+        };
+
+        // Vertical borders between clusters side by side
+        for x in (cluster_size..terrain.width()).step_by(cluster_size) {
+            for top in (0..terrain.height()).step_by(cluster_size) {
+                let bottom = (top + cluster_size).min(terrain.height());
+                let pairs = (top..bottom).map(|y| (Coordinates(x - 1, y), Coordinates(x, y))).collect::<Vec<_>>();
+                map.add_entrances(terrain, &pairs);
+            }
+        }
+
+        // Horizontal borders between clusters one above another
+        for y in (cluster_size..terrain.height()).step_by(cluster_size) {
+            for left in (0..terrain.width()).step_by(cluster_size) {
+                let right = (left + cluster_size).min(terrain.width());
+                let pairs = (left..right).map(|x| (Coordinates(x, y - 1), Coordinates(x, y))).collect::<Vec<_>>();
+                map.add_entrances(terrain, &pairs);
+            }
+        }
+
+        // Connect entrances within each cluster
+        let mut entrances: HashMap<usize, Vec<usize>> = HashMap::new();
+        for &index in map.edges.keys() {
+            let location = terrain.location_by_index(index).expect("index out of terrain");
+            entrances.entry(map.cluster(location)).or_default().push(index);
+        }
+
+        for cluster_entrances in entrances.values() {
+            for &to in cluster_entrances {
+                let to_location = terrain.location_by_index(to).expect("index out of terrain");
+                let costs = map.cluster_costs(slice::from_ref(&to_location));
+
+                for &from in cluster_entrances {
+                    if from == to {
+                        continue;
+                    }
+                    if let Some(&cost) = costs.get(&from) {
+                        map.edges.get_mut(&from).expect("no entrance").push((to, cost));
+                    }
+                }
+            }
+        }
+        map.entrances = entrances;
+
+        map
+    }
+
+    pub fn is_for(&self, terrain: &Terrain) -> bool {
+        self.terrain.fingerprint() == terrain.fingerprint()
+    }
+
+    pub fn cluster_size(&self) -> usize {
+        self.cluster_size
+    }
+
+    pub fn entrance_count(&self) -> usize {
+        self.edges.len()
+    }
+
+    fn cluster(&self, location: Location) -> usize {
+        let clusters_per_row = self.terrain.width().div_ceil(self.cluster_size);
+        (location.coordinates.1 / self.cluster_size) * clusters_per_row + location.coordinates.0 / self.cluster_size
+    }
+
+    fn cluster_entrances(&self, cluster: usize) -> &[usize] {
+        self.entrances.get(&cluster).map(|entrances| entrances.as_slice()).unwrap_or(&[])
+    }
+
+    // Costs of getting from tiles of the cluster of the first source to the nearest source without
+    // leaving the cluster, by tile index; as DistanceField but only visits one cluster
+    fn cluster_costs(&self, sources: &[Location]) -> HashMap<usize, u64> {
+        let cluster = self.cluster(sources[0]);
+        let mut costs = HashMap::new();
+        let mut queue = BinaryHeap::new();
+
+        for source in sources {
+            costs.insert(source.index(), 0);
+            queue.push(Reverse((0, source.index())));
+        }
+
+        while let Some(Reverse((cost, index))) = queue.pop() {
+            if costs.get(&index).map(|&best| cost > best).unwrap_or(false) {
+                continue;
+            }
+
+            let location = self.terrain.location_by_index(index).expect("index out of terrain");
+            // Moving from neighbour to this location costs as much as entering this location
+            let step_cost = location.movement_cost().unwrap_or(1);
+
+            for (direction, neighbour) in location.neighbours_with(self.connectivity) {
+                if location.cuts_corner(direction) || !neighbour.walkable() || self.cluster(neighbour) != cluster {
+                    continue;
+                }
+
+                let neighbour_cost = cost + step_cost;
+                if costs.get(&neighbour.index()).map(|&best| neighbour_cost < best).unwrap_or(true) {
+                    costs.insert(neighbour.index(), neighbour_cost);
+                    queue.push(Reverse((neighbour_cost, neighbour.index())));
+                }
+            }
+        }
+
+        costs
+    }
+
+    // Adds entrances for maximal runs of border tile pairs that are walkable on both sides
+    fn add_entrances(&mut self, terrain: &Terrain, pairs: &[(Coordinates, Coordinates)]) {
+        let mut runs = Vec::new();
+        let mut run = Vec::new();
+
+        for &(a, b) in pairs {
+            match (terrain.location(a), terrain.location(b)) {
+                (Some(a), Some(b)) if a.walkable() && b.walkable() => run.push((a.index(), b.index())),
+                _ => if !run.is_empty() {
+                    runs.push(run);
+                    run = Vec::new();
+                },
+            }
+        }
+        if !run.is_empty() {
+            runs.push(run);
+        }
+
+        for run in runs {
+            if run.len() >= LONG_ENTRANCE {
+                self.add_transition(terrain, run[0]);
+                self.add_transition(terrain, run[run.len() - 1]);
+            } else {
+                self.add_transition(terrain, run[run.len() / 2]);
+            }
+        }
+    }
+
+    fn add_transition(&mut self, terrain: &Terrain, (a, b): (usize, usize)) {
+        let cost = |index| {
+            terrain.location_by_index(index)
+                .and_then(|location| location.movement_cost())
+                .expect("transition not walkable")
+        };
+        self.edges.entry(a).or_default().push((b, cost(b)));
+        self.edges.entry(b).or_default().push((a, cost(a)));
+    }
+
+    // Entrance tiles to walk through on the way from given location to the nearest of arrival
+    // locations (not including these) with estimated cost of the whole path
+    pub fn abstract_path(&self, from: Location, arrivals: &[Location]) -> Option<(Vec<Location<'_>>, u64)> {
+        if arrivals.is_empty() {
+            return None;
+        }
+
+        let terrain = &self.terrain;
+        let from = terrain.location_by_index(from.index())?;
+        let start_cluster = self.cluster(from);
+
+        // Temporary edges from start location to entrances of its cluster; costs are found with
+        // single search from the start so they are of moving towards it and need to be reversed
+        let from_cost = from.movement_cost()?;
+        let from_costs = self.cluster_costs(slice::from_ref(&from));
+        let mut start_edges = Vec::new();
+        for &index in self.cluster_entrances(start_cluster) {
+            if index == from.index() {
+                continue;
+            }
+            let entrance = terrain.location_by_index(index).expect("index out of terrain");
+            if let (Some(&cost), Some(entrance_cost)) = (from_costs.get(&index), entrance.movement_cost()) {
+                start_edges.push((AbstractNode::Tile(index), cost + entrance_cost - from_cost));
+            }
+        }
+
+        // Temporary edges from entrances (and start) to arrivals in their cluster
+        let mut by_cluster: HashMap<usize, Vec<Location>> = HashMap::new();
+        for arrival in arrivals {
+            let arrival = terrain.location_by_index(arrival.index())?;
+            by_cluster.entry(self.cluster(arrival)).or_default().push(arrival);
+        }
+
+        let mut goal_edges = HashMap::new();
+        for (&cluster, cluster_arrivals) in &by_cluster {
+            let costs = self.cluster_costs(cluster_arrivals);
+            let start = if cluster == start_cluster { Some(from.index()) } else { None };
+            for index in self.cluster_entrances(cluster).iter().cloned().chain(start) {
+                if let Some(&cost) = costs.get(&index) {
+                    goal_edges.insert(index, cost);
+                }
+            }
+        }
+
+        let metric = self.connectivity.metric();
+        let (nodes, cost) = pathfinding::astar(
+            &AbstractNode::Tile(from.index()),
+            |node| {
+                let mut successors = Vec::new();
+                if let AbstractNode::Tile(index) = *node {
+                    if index == from.index() {
+                        successors.extend(start_edges.iter().cloned());
+                    }
+                    if let Some(edges) = self.edges.get(&index) {
+                        successors.extend(edges.iter().map(|&(to, cost)| (AbstractNode::Tile(to), cost)));
+                    }
+                    if let Some(&cost) = goal_edges.get(&index) {
+                        successors.push((AbstractNode::Goal, cost));
+                    }
+                }
+                successors
+            },
+            |node| match *node {
+                AbstractNode::Tile(index) => {
+                    let coordinates = terrain.coordinates(index).expect("index out of terrain");
+                    arrivals.iter()
+                        .map(|arrival| coordinates.distance(arrival.coordinates, metric) as u64)
+                        .min()
+                        .unwrap_or(0)
+                }
+                AbstractNode::Goal => 0,
+            },
+            |node| *node == AbstractNode::Goal
+        )?;
+
+        let waypoints = nodes.into_iter()
+            .filter_map(|node| match node {
+                AbstractNode::Tile(index) if index != from.index() => terrain.location_by_index(index),
+                _ => None,
+            })
+            .collect();
+
+        Some((waypoints, cost))
+    }
+}
+
+impl<'p: 'm, 'm: 's, 's: 'v, 'v> Navigator<'p, 'm, 's, 'v> {
+    // Path found over hierarchical abstraction of the terrain and refined to tile level with A*
+    // searches between consecutive entrances that do not leave the clusters of the segment ends;
+    // not always the cheapest path but much faster to find on large maps
+    pub fn find_path_hierarchical(
+        &self,
+        map: &HierarchicalMap,
+        to: &Navigator<'p, 'm, 's, 'v>,
+    ) -> Option<(Vec<Navigator<'p, 'm, 's, 'v>>, u64)> {
+        let arrivals = self.arrival_locations(slice::from_ref(to)).keys().cloned().collect::<Vec<_>>();
+        let (waypoints, _estimate) = map.abstract_path(self.location, &arrivals)?;
+        let terrain = self.game_view.terrain();
+
+        let mut path = vec![self.clone()];
+        let mut cost = 0;
+
+        let segment_ends = waypoints.iter()
+            .map(|waypoint| self.game_view.navigator(terrain.location_by_index(waypoint.index()).expect("index out of terrain")))
+            .chain(Some(to.clone()));
+
+        for segment_end in segment_ends {
+            let (segment, segment_cost) = path.last().expect("empty path").find_path_within_clusters(map, &segment_end)?;
+            path.extend(segment.into_iter().skip(1));
+            cost += segment_cost;
+        }
+
+        Some((path, cost))
+    }
+
+    // A* search that only steps on tiles in the cluster of this location or of the target
+    fn find_path_within_clusters(
+        &self,
+        map: &HierarchicalMap,
+        to: &Navigator<'p, 'm, 's, 'v>,
+    ) -> Option<(Vec<Navigator<'p, 'm, 's, 'v>>, u64)> {
+        let arrivals = self.arrival_locations(slice::from_ref(to));
+        let clusters = [map.cluster(self.location), map.cluster(to.location)];
+        let metric = self.game_view.connectivity().metric();
+        let goal = to.location.coordinates;
+        let goal_offset = if to.walkable() { 0 } else { 1 };
+
+        pathfinding::astar(
+            self,
+            |navigator| {
+                navigator.successors()
+                    .into_iter()
+                    .filter(|&(ref successor, _cost)| clusters.contains(&map.cluster(successor.location)))
+                    .collect::<Vec<_>>()
+            },
+            |navigator| navigator.location.coordinates.distance(goal, metric).saturating_sub(goal_offset) as u64,
+            |navigator| arrivals.contains_key(&navigator.location)
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use game::{TerrainBuilder, Dimension, Tile, Object, Resource, Unit, Owned, Order};
+    use game::test_support::TwoPlayers;
+
+    #[test]
+    fn hierarchical_paths() {
+        // 32x32 map with walls having gaps at different places
+        let mut terrain_builder = TerrainBuilder::new(Dimension::new(32).unwrap(), Dimension::new(32).unwrap());
+        for y in 0..30 {
+            terrain_builder = terrain_builder.place(Coordinates(10, y), Tile::Wall).unwrap();
+        }
+        for y in 2..32 {
+            terrain_builder = terrain_builder.place(Coordinates(21, y), Tile::Wall).unwrap();
+        }
+        let terrain = terrain_builder.build();
+        let players = TwoPlayers::on(terrain);
+        let p1 = &players.p1;
+
+        let game = players.game(&[
+            (Coordinates(30, 30), Object::Resources(Resource(10))),
+            (Coordinates(1, 1), Object::Unit(p1, Unit::Worker)),
+        ]);
+        let view = game.view_for(p1);
+        let navigator = |x, y| view.navigator(players.location(x, y));
+
+        let map = HierarchicalMap::new(&players.terrain, Connectivity::Four, 8);
+        assert!(map.is_for(&players.terrain));
+
+        let (path, cost) = navigator(1, 1).find_path_hierarchical(&map, &navigator(30, 30)).unwrap();
+        let (_, best_cost) = navigator(1, 1).find_path_astar(&navigator(30, 30)).unwrap();
+
+        assert!(cost >= best_cost);
+        assert!(cost <= best_cost + best_cost / 2);
+        assert_eq!(path.len() as u64, cost + 1);
+        for step in path.windows(2) {
+            assert_eq!(step[0].location.coordinates.manhattan_distance(step[1].location.coordinates), 1);
+            assert!(step[1].walkable());
+        }
+        assert_eq!(path.last().unwrap().location.coordinates.manhattan_distance(Coordinates(30, 30)), 1);
+    }
+
+    #[test]
+    fn hierarchical_path_can_be_followed() {
+        // Worker starts on rough ground; walls with gaps between clusters
+        let mut terrain_builder = TerrainBuilder::new(Dimension::new(24).unwrap(), Dimension::new(24).unwrap());
+        for y in 0..20 {
+            terrain_builder = terrain_builder.place(Coordinates(8, y), Tile::Wall).unwrap();
+        }
+        for x in 10..22 {
+            terrain_builder = terrain_builder.place(Coordinates(x, 12), Tile::Wall).unwrap();
+        }
+        for y in 14..20 {
+            for x in 2..6 {
+                terrain_builder = terrain_builder.place(Coordinates(x, y), Tile::Rough).unwrap();
+            }
+        }
+        let terrain = terrain_builder.build();
+        let players = TwoPlayers::on(terrain);
+        let p1 = &players.p1;
+
+        let mut game = players.game(&[
+            (Coordinates(22, 22), Object::Resources(Resource(10))),
+            (Coordinates(3, 15), Object::Unit(p1, Unit::Worker)),
+        ]);
+        let worker = game.entities()
+            .find(|&(_, entity)| matches!(entity.object, Object::Unit(..)))
+            .map(|(entity_id, _)| entity_id)
+            .unwrap();
+
+        let map = HierarchicalMap::new(&players.terrain, Connectivity::Four, 6);
+        let (steps, cost) = {
+            let view = game.view_for(p1);
+            let navigator = |x, y| view.navigator(players.location(x, y));
+            let (path, cost) = navigator(3, 15).find_path_hierarchical(&map, &navigator(22, 22)).unwrap();
+            assert_eq!(path[0], navigator(3, 15));
+            (path.iter().map(|navigator| navigator.location).collect::<Vec<_>>(), cost)
+        };
+
+        // Engine accepts every step and the costs add up
+        let mut total = 0;
+        for step in steps.windows(2) {
+            let direction = step[0].direction_to(step[1]).unwrap();
+            let order = Owned(p1, Order::Move(worker, direction));
+            let violations = game.apply(Some(order).into_iter());
+            assert!(violations.is_empty(), "{:?}", violations);
+
+            let step_cost = step[1].movement_cost().unwrap();
+            for _ in 1..step_cost {
+                game.apply(None.into_iter());
+            }
+            total += step_cost;
+        }
+        assert_eq!(total, cost);
+        assert_eq!(game.get_entity(worker).unwrap().location, *steps.last().unwrap());
+        assert_eq!(steps.last().unwrap().coordinates.manhattan_distance(Coordinates(22, 22)), 1);
+    }
+}
@@ -0,0 +1,159 @@
+use game::{Terrain, Location, Coordinates, Metric, Object, Resource};
+use game_view::GameView;
+
+// How influence of an entity weakens with distance: value is multiplied by factor for every step
+// beyond entity's reach and drops to zero after radius steps
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Decay {
+    pub factor: f64,
+    pub radius: usize,
+}
+
+impl Default for Decay {
+    fn default() -> Decay {
+        Decay {
+            factor: 0.5,
+            radius: 3,
+        }
+    }
+}
+
+// Value per tile (by location index)
+#[derive(Debug, Clone)]
+pub struct InfluenceMap {
+    values: Vec<f64>,
+}
+
+impl InfluenceMap {
+    fn new(terrain: &Terrain) -> InfluenceMap {
+        InfluenceMap {
+            values: vec![0.0; terrain.size()],
+        }
+    }
+
+    // Adds full value within reach of center and decaying value past it to tiles in sight of center
+    fn spread(&mut self, terrain: &Terrain, center: Coordinates, value: f64, reach: usize, decay: Decay, metric: Metric) {
+        for location in terrain.within(center, reach + decay.radius, metric) {
+            if !terrain.line_of_sight(center, location.coordinates) {
+                continue;
+            }
+            let distance = location.coordinates.distance(center, metric);
+            let steps = distance.saturating_sub(reach);
+            self.values[location.index()] += value * decay.factor.powi(steps as i32);
+        }
+    }
+
+    pub fn value(&self, location: Location) -> f64 {
+        self.values.get(location.index()).cloned().unwrap_or(0.0)
+    }
+}
+
+// Influence of entities on the game view player's situation at every tile. Distances are measured
+// under current movement rules ignoring obstacles, but walls block influence.
+#[derive(Debug, Clone)]
+pub struct InfluenceMaps {
+    // Damage my units can deal at the tile
+    pub strength: InfluenceMap,
+    // Damage enemy units can deal at the tile
+    pub threat: InfluenceMap,
+    // Amount of resources around the tile
+    pub resources: InfluenceMap,
+}
+
+impl InfluenceMaps {
+    // Positive where I am stronger than the enemy
+    pub fn balance(&self, location: Location) -> f64 {
+        self.strength.value(location) - self.threat.value(location)
+    }
+
+    pub fn is_safe(&self, location: Location) -> bool {
+        self.threat.value(location) <= 0.0
+    }
+}
+
+impl<'p: 's, 'm: 's, 's> GameView<'p, 'm, 's> {
+    // Units project their damage over their attack range plus one move as they can step in and hit;
+    // resources project their amount
+    pub fn influence_maps(&self, decay: Decay) -> InfluenceMaps {
+        let terrain = self.terrain();
+        let metric = self.connectivity().metric();
+
+        let mut maps = InfluenceMaps {
+            strength: InfluenceMap::new(terrain),
+            threat: InfluenceMap::new(terrain),
+            resources: InfluenceMap::new(terrain),
+        };
+
+        for (_entity_id, entity) in self.entities() {
+            let center = entity.location.coordinates;
+            match entity.object {
+                Object::Unit(_, ref unit) => {
+                    let map = if self.is_mine(entity) { &mut maps.strength } else { &mut maps.threat };
+                    map.spread(terrain, center, unit.damage() as f64, unit.attack_range() + 1, decay, metric);
+                }
+                Object::Resources(Resource(amount)) => {
+                    maps.resources.spread(terrain, center, amount as f64, 0, decay, metric);
+                }
+                Object::Building(..) => (),
+            }
+        }
+
+        maps
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use game::{TerrainBuilder, Dimension, Tile, Unit};
+    use game::test_support::TwoPlayers;
+
+    #[test]
+    fn influence_maps() {
+        let players = TwoPlayers::default();
+        let (p1, p2) = (&players.p1, &players.p2);
+
+        let game = players.game(&[
+            (Coordinates(0, 0), Object::Resources(Resource(10))),
+            (Coordinates(1, 1), Object::Unit(p1, Unit::Worker)),
+            (Coordinates(6, 6), Object::Unit(p2, Unit::Heavy)),
+        ]);
+        let view = game.view_for(p1);
+        let location = |x, y| players.location(x, y);
+
+        let maps = view.influence_maps(Decay { factor: 0.5, radius: 2 });
+
+        // Heavy can step and hit anything within 2 tiles
+        assert_eq!(maps.threat.value(location(6, 4)), 4.0);
+        assert_eq!(maps.threat.value(location(6, 3)), 2.0);
+        assert_eq!(maps.threat.value(location(6, 2)), 1.0);
+        assert_eq!(maps.threat.value(location(6, 1)), 0.0);
+        assert!(maps.is_safe(location(1, 1)));
+        assert!(!maps.is_safe(location(5, 5)));
+
+        assert_eq!(maps.strength.value(location(1, 2)), 1.0);
+        assert!(maps.balance(location(1, 1)) > 0.0);
+        assert!(maps.balance(location(5, 6)) < 0.0);
+
+        assert_eq!(maps.resources.value(location(0, 0)), 10.0);
+        assert_eq!(maps.resources.value(location(1, 0)), 5.0);
+        assert_eq!(maps.resources.value(location(3, 0)), 0.0);
+    }
+
+    #[test]
+    fn influence_is_blocked_by_walls_but_not_water() {
+        let terrain = TerrainBuilder::new(Dimension::new(3).unwrap(), Dimension::new(3).unwrap())
+            .place(Coordinates(0, 1), Tile::Wall).unwrap()
+            .place(Coordinates(1, 1), Tile::Water).unwrap()
+            .build();
+        let players = TwoPlayers::on(terrain);
+        let (p1, p2) = (&players.p1, &players.p2);
+
+        let game = players.game(&[(Coordinates(0, 0), Object::Unit(p2, Unit::Heavy))]);
+        let location = |x, y| players.location(x, y);
+
+        let maps = game.view_for(p1).influence_maps(Decay { factor: 0.5, radius: 2 });
+        assert_eq!(maps.threat.value(location(0, 2)), 0.0);
+        assert_eq!(maps.threat.value(location(2, 2)), 1.0);
+    }
+}
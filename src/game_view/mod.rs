@@ -1,8 +1,27 @@
 use pathfinding;
-use game::{GameState, Entity, EntityID, Object, Unit, EntitiesIter, Player, Location, Direction, Resource};
+use game::{GameState, Entity, EntityID, Object, Unit, EntitiesIter, EntitiesInAreaIter, Player, Terrain,
+           Location, Direction, Connectivity, Metric, Resource, Order};
 use std::ptr;
 use std::hash::{Hash, Hasher};
 use std::fmt;
+use std::collections::HashMap;
+use std::slice;
+
+mod path_cache;
+mod distance_field;
+mod cooperative;
+mod hierarchical;
+mod terrain_analysis;
+mod influence;
+mod simulation;
+
+pub use self::path_cache::PathCache;
+pub use self::distance_field::{DistanceField, DistanceFieldCache};
+pub use self::cooperative::{ReservationTable, CooperativePlan};
+pub use self::hierarchical::HierarchicalMap;
+pub use self::terrain_analysis::{TerrainAnalysis, Chokepoint, ExpansionSite};
+pub use self::influence::{InfluenceMap, InfluenceMaps, Decay};
+pub use self::simulation::Simulation;
 
 #[derive(Debug)]
 pub struct GameView<'p: 's, 'm: 's, 's> {
@@ -13,8 +32,8 @@ pub struct GameView<'p: 's, 'm: 's, 's> {
 impl<'p: 's, 'm: 's, 's> GameView<'p, 'm, 's> {
     pub fn new(game: &'s GameState<'p, 'm>, player: &'p Player) -> GameView<'p, 'm, 's> {
         GameView {
-            game: game,
-            player: player,
+            game,
+            player,
         }
     }
 
@@ -22,15 +41,150 @@ impl<'p: 's, 'm: 's, 's> GameView<'p, 'm, 's> {
         let entity = self.game.get_entity_by_location(location);
         Navigator {
             game_view: self,
-            location: location,
-            entity: entity,
+            location,
+            entity,
         }
     }
 
+    pub fn terrain(&self) -> &'m Terrain {
+        self.game.terrain()
+    }
+
+    pub fn connectivity(&self) -> Connectivity {
+        self.game.connectivity()
+    }
+
     pub fn entities(&self) -> EntitiesIter<'p, 'm, 's> {
         self.game.entities()
     }
 
+    pub fn entity(&self, entity_id: EntityID) -> Option<&'s Entity<'m, 'p>> {
+        self.game.get_entity(entity_id)
+    }
+
+    pub fn cycle(&self) -> u64 {
+        self.game.cycle()
+    }
+
+    pub fn busy_for(&self, entity_id: EntityID) -> u64 {
+        self.game.busy_for(entity_id)
+    }
+
+    // Resources the worker has harvested and not returned yet
+    pub fn carried(&self, entity_id: EntityID) -> u64 {
+        self.game.carried(entity_id)
+    }
+
+    // Resources stored in my bases
+    pub fn my_resources(&self) -> u64 {
+        self.game.resources_of(self.player)
+    }
+
+    pub fn is_mine(&self, entity: &Entity<'m, 'p>) -> bool {
+        entity.object.owner().map(|owner| ptr::eq(owner, self.player)).unwrap_or(false)
+    }
+
+    pub fn is_enemy(&self, entity: &Entity<'m, 'p>) -> bool {
+        entity.object.owner().map(|owner| !ptr::eq(owner, self.player)).unwrap_or(false)
+    }
+
+    // Players owning any entity other than me, ordered by their lowest entity ID so that the order
+    // does not depend on hashing
+    pub fn enemies(&self) -> Vec<&'p Player> {
+        let mut enemies: Vec<(EntityID, &'p Player)> = Vec::new();
+        for (entity_id, entity) in self.entities() {
+            if let Some(owner) = entity.object.owner() {
+                if ptr::eq(owner, self.player) {
+                    continue;
+                }
+                match enemies.iter_mut().find(|&&mut (_, enemy)| ptr::eq(enemy, owner)) {
+                    Some(&mut (ref mut lowest, _)) => {
+                        if entity_id.0 < lowest.0 {
+                            *lowest = entity_id;
+                        }
+                    }
+                    None => enemies.push((entity_id, owner)),
+                }
+            }
+        }
+        enemies.sort_by_key(|&(entity_id, _)| entity_id.0);
+        enemies.into_iter().map(|(_, enemy)| enemy).collect()
+    }
+
+    // Number of steps between locations on empty terrain under current movement rules
+    pub fn distance(&self, from: Location<'m>, to: Location<'m>) -> usize {
+        from.coordinates.distance(to.coordinates, self.connectivity().metric())
+    }
+
+    pub fn entities_within(&self, center: Location<'m>, radius: usize, metric: Metric) -> EntitiesInAreaIter<'p, 'm, 's> {
+        self.game.entities_in_area(self.game.terrain().within(center.coordinates, radius, metric))
+    }
+
+    // Searches rings of increasing distance around center for first entity matching predicate
+    pub fn closest_entity<F>(&self, center: Location<'m>, metric: Metric, predicate: F) -> Option<&'s Entity<'m, 'p>>
+    where
+        F: Fn(&Entity<'m, 'p>) -> bool,
+    {
+        let terrain = self.game.terrain();
+        // Furthest any tile can be from center
+        let max_radius = match metric {
+            Metric::Manhattan => terrain.width() + terrain.height(),
+            Metric::Chebyshev => terrain.width().max(terrain.height()),
+        };
+
+        (0..max_radius + 1)
+            .flat_map(|radius| self.game.entities_in_area(terrain.ring(center.coordinates, radius, metric)))
+            .find(|entity| predicate(entity))
+    }
+
+    pub fn closest_enemy(&self, center: Location<'m>) -> Option<&'s Entity<'m, 'p>> {
+        self.closest_entity(center, self.connectivity().metric(), |entity| self.is_enemy(entity))
+    }
+
+    // Neighbouring locations that can be moved onto right now
+    pub fn free_neighbours(&self, location: Location<'m>) -> Vec<Location<'m>> {
+        location.neighbours_with(self.connectivity())
+            .filter(|&(direction, _)| !location.cuts_corner(direction))
+            .map(|(_, neighbour)| neighbour)
+            .filter(|&neighbour| neighbour.walkable() && self.game.get_entity_by_location(neighbour).is_none())
+            .collect()
+    }
+
+    // Orders for the entity that would be executed without rule violation this cycle
+    pub fn legal_orders(&self, entity_id: EntityID) -> Vec<Order> {
+        self.game.legal_orders(self.player, entity_id)
+    }
+
+    // Legal orders of all my units and buildings that can be given any; entities are listed in
+    // order of their IDs
+    pub fn all_legal_orders(&self) -> Vec<(EntityID, Vec<Order>)> {
+        let mut orders = self.entities()
+            .filter(|&(_, entity)| self.is_mine(entity))
+            .map(|(entity_id, _)| (entity_id, self.legal_orders(entity_id)))
+            .filter(|(_, orders)| !orders.is_empty())
+            .collect::<Vec<_>>();
+        orders.sort_by_key(|&(EntityID(id), _)| id);
+        orders
+    }
+
+    // Distance field that treats entities other than at sources as obstacles; to be recomputed when
+    // they move
+    pub fn distance_field(&self, sources: &[Location<'m>]) -> DistanceField {
+        DistanceField::with_obstacles(self.terrain(), self.connectivity(), sources, |location| {
+            self.game.get_entity_by_location(location).is_some()
+        })
+    }
+
+    // Distance field over terrain only, computed once per set of sources and kept in cache
+    pub fn static_distance_field<'c>(&self, cache: &'c mut DistanceFieldCache, sources: &[Location<'m>]) -> &'c DistanceField {
+        cache.get(self.terrain(), self.connectivity(), sources)
+    }
+
+    // Hierarchical abstraction of the terrain; to be built once per map and kept between cycles
+    pub fn hierarchical_map(&self, cluster_size: usize) -> HierarchicalMap {
+        HierarchicalMap::new(self.terrain(), self.connectivity(), cluster_size)
+    }
+
     pub fn my_units<'v>(&'v self) -> MyUnitIter<'p, 'm, 's, 'v> {
         MyUnitIter {
             game_view: self,
@@ -68,8 +222,8 @@ impl<'p: 'm, 'm: 's, 's: 'v, 'v> Iterator for MyUnitIter<'p, 'm, 's, 'v> {
                         //TODO: should that be impl Eq for Player?
                         if ptr::eq(owner, self.game_view.player) => {
                             return Some(MyUnit {
-                                entity_id: entity_id,
-                                unit: unit,
+                                entity_id,
+                                unit,
                                 navigator: self.game_view.navigator(location)
                             })
                         }
@@ -102,8 +256,8 @@ impl<'p: 'm, 'm: 's, 's: 'v, 'v> Iterator for ResourcesIter<'p, 'm, 's, 'v> {
                 match entity {
                     &Entity { location, object: Object::Resources(ref resource), .. } => {
                         return Some(Resources {
-                            entity_id: entity_id,
-                            resource: resource,
+                            entity_id,
+                            resource,
                             navigator: self.game_view.navigator(location)
                         })
                     }
@@ -147,7 +301,7 @@ impl<'p: 'm, 'm: 's, 's: 'v, 'v> Clone for Navigator<'p, 'm, 's, 'v> {
     fn clone(&self) -> Self {
         Navigator {
             game_view: self.game_view,
-            location: self.location.clone(),
+            location: self.location,
             entity: self.entity
         }
     }
@@ -160,24 +314,230 @@ impl<'p: 'm, 'm: 's, 's: 'v, 'v> Navigator<'p, 'm, 's, 'v> {
         })
     }
 
+    // Navigators that can be moved to from this one with cost of the move
+    pub fn successors(&self) -> Vec<(Navigator<'p, 'm, 's, 'v>, u64)> {
+        let location = self.location;
+        location.neighbours_with(self.game_view.connectivity())
+            .filter(|&(direction, _location)| !location.cuts_corner(direction))
+            .map(|(_direction, location)| self.game_view.navigator(location))
+            .filter(|target| target.walkable())
+            .map(|target| {
+                let cost = target.location.movement_cost().expect("walkable location has movement cost");
+                (target, cost)
+            })
+            .collect()
+    }
+
+    // Locations at which a path to given goals ends mapped to index of the goal: goal location
+    // itself if it can be walked on, otherwise locations next to it
+    fn arrival_locations(&self, goals: &[Navigator<'p, 'm, 's, 'v>]) -> HashMap<Location<'m>, usize> {
+        let connectivity = self.game_view.connectivity();
+        let mut arrivals = HashMap::new();
+
+        for (goal_index, goal) in goals.iter().enumerate() {
+            if goal.walkable() {
+                arrivals.entry(goal.location).or_insert(goal_index);
+            } else {
+                for (_direction, location) in goal.location.neighbours_with(connectivity) {
+                    arrivals.entry(location).or_insert(goal_index);
+                }
+            }
+        }
+
+        arrivals
+    }
+
     pub fn find_path_dijkstra(&self, to: &Navigator<'p, 'm, 's, 'v>) -> Option<(Vec<Navigator<'p, 'm, 's, 'v>>, u64)> {
-        let to_neighbour_locations = to.location.neighbours().map(|(_direction, location)| location).collect::<Vec<_>>();
+        self.find_path_to_nearest(slice::from_ref(to))
+            .map(|(path, cost, _goal)| (path, cost))
+    }
 
-        // TODO: if to is not walkable use neighbour location else use to directly
-        pathfinding::dijkstra(
+    // A* search guided by distance to the goal under current movement rules; finds the same cost
+    // paths as find_path_dijkstra while visiting fewer locations
+    pub fn find_path_astar(&self, to: &Navigator<'p, 'm, 's, 'v>) -> Option<(Vec<Navigator<'p, 'm, 's, 'v>>, u64)> {
+        let arrivals = self.arrival_locations(slice::from_ref(to));
+        if arrivals.is_empty() {
+            return None;
+        }
+
+        let metric = self.game_view.connectivity().metric();
+        let goal = to.location.coordinates;
+        // Path to unwalkable goal ends next to it
+        let goal_offset = if to.walkable() { 0 } else { 1 };
+
+        pathfinding::astar(
             self,
-            |navigator| navigator.location.neighbours()
-                .map(|(_direction, location)| (self.game_view.navigator(location), 1))
-                .filter(|&(ref target, _)| target.walkable() || target == to),
-            |navigator| to_neighbour_locations.contains(&navigator.location)
+            |navigator| navigator.successors(),
+            |navigator| navigator.location.coordinates.distance(goal, metric).saturating_sub(goal_offset) as u64,
+            |navigator| arrivals.contains_key(&navigator.location)
         )
     }
 
+    // Single search for the cheapest to reach of given goals; returns path, its cost and the goal
+    // that was reached
+    pub fn find_path_to_nearest(
+        &self,
+        goals: &[Navigator<'p, 'm, 's, 'v>],
+    ) -> Option<(Vec<Navigator<'p, 'm, 's, 'v>>, u64, Navigator<'p, 'm, 's, 'v>)> {
+        let arrivals = self.arrival_locations(goals);
+        if arrivals.is_empty() {
+            return None;
+        }
+
+        pathfinding::dijkstra(
+            self,
+            |navigator| navigator.successors(),
+            |navigator| arrivals.contains_key(&navigator.location)
+        ).map(|(path, cost)| {
+            let goal_index = arrivals[&path.last().expect("empty path").location];
+            (path, cost, goals[goal_index].clone())
+        })
+    }
+
     pub fn direction_to(&self, to: &Navigator<'p, 'm, 's, 'v>) -> Option<Direction> {
-        self.location.direction_to(to.location)
+        self.location.direction_to_with(to.location, self.game_view.connectivity())
     }
 
     pub fn walkable(&self) -> bool {
         self.entity.is_none() && self.location.walkable()
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use game::{GameStateBuilder, TerrainBuilder, Coordinates, Colour, Building, Owned};
+    use game::test_support::TwoPlayers;
+
+    #[test]
+    fn spatial_queries() {
+        let players = TwoPlayers::default();
+        let (p1, p2) = (&players.p1, &players.p2);
+
+        let game = players.game(&[
+            (Coordinates(0, 0), Object::Resources(Resource(10))),
+            (Coordinates(2, 1), Object::Building(p1, Building::Base(Resource(10)))),
+            (Coordinates(2, 2), Object::Unit(p1, Unit::Worker)),
+            (Coordinates(5, 5), Object::Unit(p2, Unit::Worker)),
+            (Coordinates(6, 6), Object::Unit(p2, Unit::Light)),
+        ]);
+        let view = game.view_for(p1);
+        let location = |x, y| players.location(x, y);
+
+        assert_eq!(view.entities_within(location(2, 2), 1, Metric::Manhattan).count(), 2);
+        assert_eq!(view.entities_within(location(2, 2), 4, Metric::Manhattan).count(), 3);
+        assert_eq!(view.entities_within(location(2, 2), 3, Metric::Chebyshev).count(), 4);
+
+        assert_eq!(
+            view.closest_enemy(location(7, 7)).map(|entity| entity.location.coordinates),
+            Some(Coordinates(6, 6))
+        );
+        assert_eq!(
+            view.closest_enemy(location(2, 2)).map(|entity| entity.location.coordinates),
+            Some(Coordinates(5, 5))
+        );
+
+        assert_eq!(
+            view.free_neighbours(location(2, 1)),
+            vec![location(2, 0), location(3, 1), location(1, 1)]
+        );
+    }
+
+    #[test]
+    fn closest_enemy_in_opposite_corner() {
+        let players = TwoPlayers::default();
+        let (p1, p2) = (&players.p1, &players.p2);
+
+        let game = players.game(&[
+            (Coordinates(0, 0), Object::Unit(p1, Unit::Worker)),
+            (Coordinates(7, 7), Object::Unit(p2, Unit::Worker)),
+        ]);
+        let view = game.view_for(p1);
+        let location = |x, y| players.location(x, y);
+
+        // 14 steps away under Manhattan metric
+        assert_eq!(
+            view.closest_enemy(location(0, 0)).map(|entity| entity.location.coordinates),
+            Some(Coordinates(7, 7))
+        );
+        assert_eq!(
+            view.closest_entity(location(0, 0), Metric::Chebyshev, |entity| view.is_enemy(entity))
+                .map(|entity| entity.location.coordinates),
+            Some(Coordinates(7, 7))
+        );
+    }
+
+    #[test]
+    fn enemies_in_stable_order() {
+        let terrain = TerrainBuilder::terrain_8x8_wall1();
+        let players = (0..6).map(|i| Player::new(format!("P{}", i), Colour::Red)).collect::<Vec<_>>();
+
+        let mut builder = GameStateBuilder::new("test", &terrain);
+        for (x, player) in players.iter().enumerate() {
+            builder
+                .place(Coordinates(x, 0), Object::Unit(player, Unit::Worker)).unwrap()
+                .place(Coordinates(x, 7), Object::Unit(player, Unit::Worker)).unwrap();
+        }
+        let game = builder.build_for_round(0);
+
+        let names = game.view_for(&players[2]).enemies().iter()
+            .map(|player| player.name.clone())
+            .collect::<Vec<_>>();
+        assert_eq!(names, vec!["P0", "P1", "P3", "P4", "P5"]);
+    }
+
+    #[test]
+    fn find_path_to_nearest_goal() {
+        let players = TwoPlayers::default();
+        let p1 = &players.p1;
+
+        let game = players.game(&[
+            (Coordinates(0, 0), Object::Resources(Resource(10))),
+            (Coordinates(7, 7), Object::Resources(Resource(10))),
+            (Coordinates(5, 5), Object::Unit(p1, Unit::Worker)),
+        ]);
+        let view = game.view_for(p1);
+        let navigator = |x, y| view.navigator(players.location(x, y));
+
+        let goals = vec![navigator(0, 0), navigator(7, 7)];
+        let (path, cost, goal) = navigator(5, 5).find_path_to_nearest(&goals).unwrap();
+        assert_eq!(goal, navigator(7, 7));
+        assert_eq!(cost, 3);
+        assert_eq!(path.last().unwrap().location.coordinates.manhattan_distance(Coordinates(7, 7)), 1);
+
+        // walkable goal is reached directly
+        let (path, cost) = navigator(5, 5).find_path_dijkstra(&navigator(5, 7)).unwrap();
+        assert_eq!(cost, 2);
+        assert_eq!(path.last().unwrap(), &navigator(5, 7));
+    }
+
+    #[test]
+    fn legal_orders() {
+        let players = TwoPlayers::default();
+        let (p1, p2) = (&players.p1, &players.p2);
+
+        let mut game = players.game(&[
+            (Coordinates(0, 0), Object::Unit(p1, Unit::Worker)),
+            (Coordinates(1, 0), Object::Building(p1, Building::Barracks)),
+            (Coordinates(4, 4), Object::Unit(p1, Unit::Light)),
+            (Coordinates(6, 6), Object::Unit(p2, Unit::Worker)),
+        ]);
+        let light = game.view_for(p1).all_legal_orders()[1].0;
+
+        {
+            let view = game.view_for(p1);
+            let all = view.all_legal_orders();
+            assert_eq!(all.len(), 2);
+            assert_eq!(all[0].1, vec![Order::Move(all[0].0, Direction::Down)]);
+            // Walls at (4, 3) and (3, 4)
+            assert_eq!(all[1].1, vec![Order::Move(light, Direction::Right), Order::Move(light, Direction::Down)]);
+            assert!(view.legal_orders(view.entities().find(|&(_, entity)| view.is_enemy(entity)).unwrap().0).is_empty());
+        }
+
+        // Legal order is executed without violation
+        let orders = game.view_for(p1).legal_orders(light);
+        assert!(game.apply(orders.into_iter().take(1).map(|order| Owned(p1, order))).is_empty());
+        assert_eq!(game.get_entity(light).unwrap().location.coordinates, Coordinates(5, 4));
+        assert_eq!(game.view_for(p1).legal_orders(light).len(), 4);
+    }
+}
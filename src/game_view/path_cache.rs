@@ -0,0 +1,173 @@
+use std::collections::HashMap;
+
+use game::EntityID;
+use game_view::Navigator;
+
+#[derive(Debug, Clone)]
+struct CachedPath {
+    // Location index of the goal the path leads to
+    goal: usize,
+    // Location indices of the path including the starting location
+    steps: Vec<usize>,
+}
+
+// Remembers paths found for units so they can be followed over many cycles; a path is re-planned
+// only when the unit leaves it, its goal changes or another entity moves onto the remaining part
+// of it. Cached paths are dropped when used with different terrain.
+#[derive(Debug, Default, Clone)]
+pub struct PathCache {
+    // Fingerprint of the terrain paths were found on
+    terrain: Option<u64>,
+    paths: HashMap<EntityID, CachedPath>,
+}
+
+impl PathCache {
+    pub fn new() -> PathCache {
+        Default::default()
+    }
+
+    pub fn len(&self) -> usize {
+        self.paths.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.paths.is_empty()
+    }
+
+    pub fn invalidate(&mut self, entity_id: EntityID) {
+        self.paths.remove(&entity_id);
+    }
+
+    pub fn clear(&mut self) {
+        self.paths.clear();
+    }
+
+    // Path (starting with from) that entity should follow to get to given goal
+    pub fn path_to<'p: 'm, 'm: 's, 's: 'v, 'v>(
+        &mut self,
+        entity_id: EntityID,
+        from: &Navigator<'p, 'm, 's, 'v>,
+        to: &Navigator<'p, 'm, 's, 'v>,
+    ) -> Option<Vec<Navigator<'p, 'm, 's, 'v>>> {
+        self.check_terrain(from);
+
+        if let Some(path) = self.cached_path(entity_id, from, |goal| goal == to.location.index()) {
+            return Some(path);
+        }
+
+        self.paths.remove(&entity_id);
+        from.find_path_astar(to).map(|(path, _cost)| {
+            self.store(entity_id, &path, to);
+            path
+        })
+    }
+
+    // Path (starting with from) that entity should follow to get to one of given goals together
+    // with the goal; cached path is kept as long as its goal is still one of the goals
+    pub fn path_to_nearest<'p: 'm, 'm: 's, 's: 'v, 'v>(
+        &mut self,
+        entity_id: EntityID,
+        from: &Navigator<'p, 'm, 's, 'v>,
+        goals: &[Navigator<'p, 'm, 's, 'v>],
+    ) -> Option<(Vec<Navigator<'p, 'm, 's, 'v>>, Navigator<'p, 'm, 's, 'v>)> {
+        self.check_terrain(from);
+
+        let mut reached_goal = None;
+        if let Some(path) = self.cached_path(entity_id, from, |goal| {
+            reached_goal = goals.iter().find(|navigator| navigator.location.index() == goal);
+            reached_goal.is_some()
+        }) {
+            return reached_goal.map(|goal| (path, goal.clone()));
+        }
+
+        self.paths.remove(&entity_id);
+        from.find_path_to_nearest(goals).map(|(path, _cost, goal)| {
+            self.store(entity_id, &path, &goal);
+            (path, goal)
+        })
+    }
+
+    fn check_terrain(&mut self, navigator: &Navigator) {
+        let fingerprint = navigator.game_view.terrain().fingerprint();
+        if self.terrain != Some(fingerprint) {
+            self.paths.clear();
+            self.terrain = Some(fingerprint);
+        }
+    }
+
+    fn store(&mut self, entity_id: EntityID, path: &[Navigator], goal: &Navigator) {
+        self.paths.insert(entity_id, CachedPath {
+            goal: goal.location.index(),
+            steps: path.iter().map(|navigator| navigator.location.index()).collect(),
+        });
+    }
+
+    // Remaining part of cached path if entity is still on it, goal is accepted and no other
+    // entity is standing in the way
+    fn cached_path<'p: 'm, 'm: 's, 's: 'v, 'v, G>(
+        &self,
+        entity_id: EntityID,
+        from: &Navigator<'p, 'm, 's, 'v>,
+        accept_goal: G,
+    ) -> Option<Vec<Navigator<'p, 'm, 's, 'v>>>
+    where
+        G: FnOnce(usize) -> bool,
+    {
+        let cached = self.paths.get(&entity_id)?;
+        if !accept_goal(cached.goal) {
+            return None;
+        }
+
+        let position = cached.steps.iter().position(|&index| index == from.location.index())?;
+        let terrain = from.game_view.terrain();
+
+        let mut path = vec![from.clone()];
+        for &index in &cached.steps[position + 1..] {
+            let navigator = from.game_view.navigator(terrain.location_by_index(index)?);
+            if !navigator.walkable() {
+                return None;
+            }
+            path.push(navigator);
+        }
+        Some(path)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use game::{Coordinates, Object, Resource, Unit};
+    use game::test_support::TwoPlayers;
+
+    #[test]
+    fn astar_and_path_cache() {
+        let players = TwoPlayers::default();
+        let p1 = &players.p1;
+
+        let game = players.game(&[
+            (Coordinates(7, 7), Object::Resources(Resource(10))),
+            (Coordinates(0, 0), Object::Unit(p1, Unit::Worker)),
+        ]);
+        let view = game.view_for(p1);
+        let navigator = |x, y| view.navigator(players.location(x, y));
+
+        let (path, cost) = navigator(0, 0).find_path_astar(&navigator(7, 7)).unwrap();
+        let (_, dijkstra_cost) = navigator(0, 0).find_path_dijkstra(&navigator(7, 7)).unwrap();
+        assert_eq!(cost, dijkstra_cost);
+        assert_eq!(path.len() as u64, cost + 1);
+
+        let worker = EntityID(1);
+        let mut cache = PathCache::new();
+        let path = cache.path_to(worker, &navigator(0, 0), &navigator(7, 7)).unwrap();
+        assert_eq!(cache.len(), 1);
+
+        // continuing from second step of the path reuses the rest of it
+        let rest = cache.path_to(worker, &path[1], &navigator(7, 7)).unwrap();
+        assert_eq!(&path[1..], rest.as_slice());
+
+        // leaving the path causes re-planning
+        let off_path = if path.contains(&navigator(1, 0)) { navigator(0, 1) } else { navigator(1, 0) };
+        let path = cache.path_to(worker, &off_path, &navigator(7, 7)).unwrap();
+        assert_eq!(path[0], off_path);
+    }
+}
@@ -0,0 +1,97 @@
+use game::{GameState, GameRuleViolation, Player, Owned, Order};
+use game_view::GameView;
+
+// Private copy of the game state that AI can play forward without affecting the real game; orders
+// are applied by the same rules as in the game itself
+#[derive(Debug, Clone)]
+pub struct Simulation<'p, 'm> {
+    game: GameState<'p, 'm>,
+}
+
+impl<'p, 'm> Simulation<'p, 'm> {
+    pub fn new(game: &GameState<'p, 'm>) -> Simulation<'p, 'm> {
+        Simulation {
+            game: game.clone(),
+        }
+    }
+
+    pub fn cycle(&self) -> u64 {
+        self.game.cycle()
+    }
+
+    pub fn state(&self) -> &GameState<'p, 'm> {
+        &self.game
+    }
+
+    pub fn view_for<'s>(&'s self, player: &'p Player) -> GameView<'p, 'm, 's> {
+        self.game.view_for(player)
+    }
+
+    // Applies orders of any players for one cycle
+    pub fn apply<A>(&mut self, orders: A) -> Vec<GameRuleViolation<'p, 'm>>
+    where
+        A: Iterator<Item = Owned<'p, Order>>,
+    {
+        self.game.apply(orders)
+    }
+
+    // Lets given number of cycles pass with no new orders
+    pub fn advance(&mut self, cycles: u64) {
+        for _ in 0..cycles {
+            self.game.apply(None.into_iter());
+        }
+    }
+}
+
+impl<'p: 's, 'm: 's, 's> GameView<'p, 'm, 's> {
+    pub fn simulation(&self) -> Simulation<'p, 'm> {
+        Simulation::new(self.game)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use game::{TerrainBuilder, Dimension, Tile, Unit, Object, Coordinates, Direction};
+    use game::test_support::TwoPlayers;
+
+    #[test]
+    fn simulation_does_not_affect_game() {
+        let mut terrain_builder = TerrainBuilder::new(Dimension::new(4).unwrap(), Dimension::new(4).unwrap());
+        terrain_builder = terrain_builder.place(Coordinates(1, 0), Tile::Rough).unwrap();
+        let terrain = terrain_builder.build();
+        let players = TwoPlayers::on(terrain);
+        let (p1, p2) = (&players.p1, &players.p2);
+
+        let game = players.game(&[
+            (Coordinates(0, 0), Object::Unit(p1, Unit::Worker)),
+            (Coordinates(3, 3), Object::Unit(p2, Unit::Worker)),
+        ]);
+        let view = game.view_for(p1);
+        let worker = view.my_units().next().unwrap().entity_id;
+        let enemy = view.entities().find(|&(_, entity)| view.is_enemy(entity)).unwrap().0;
+
+        let mut simulation = view.simulation();
+        let orders = vec![
+            Owned(p2, Order::Move(worker, Direction::Down)),
+            Owned(p1, Order::Move(worker, Direction::Right)),
+            Owned(p2, Order::Move(enemy, Direction::Up)),
+        ];
+        match simulation.apply(orders.into_iter()).as_slice() {
+            &[GameRuleViolation::EntityNotOwned(id, _)] => assert_eq!(id, worker),
+            violations => panic!("unexpected violations: {:?}", violations),
+        }
+        assert_eq!(simulation.cycle(), 1);
+        assert_eq!(simulation.view_for(p2).busy_for(worker), 1);
+        assert_eq!(simulation.state().get_entity(enemy).unwrap().location.coordinates, Coordinates(3, 2));
+
+        simulation.advance(1);
+        assert_eq!(simulation.cycle(), 2);
+        assert_eq!(simulation.view_for(p1).busy_for(worker), 0);
+
+        // Real game is untouched
+        assert_eq!(game.cycle(), 0);
+        assert_eq!(game.get_entity(worker).unwrap().location.coordinates, Coordinates(0, 0));
+        assert_eq!(game.get_entity(enemy).unwrap().location.coordinates, Coordinates(3, 3));
+    }
+}
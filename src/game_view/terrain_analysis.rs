@@ -0,0 +1,298 @@
+use std::collections::{HashMap, VecDeque};
+
+use game::{Terrain, Location, Coordinates, Connectivity, Object, Building, Resource};
+use game_view::GameView;
+
+// Narrow passage between two regions
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct Chokepoint {
+    // Tile in the middle of the narrowest part of the passage
+    pub coordinates: Coordinates,
+    pub regions: (usize, usize),
+    // Distance from chokepoint tile to the nearest tile that cannot be walked on
+    pub clearance: usize,
+}
+
+// Place where new base could be built scored by resources nearby
+#[derive(Debug, PartialEq, Clone)]
+pub struct ExpansionSite {
+    pub coordinates: Coordinates,
+    pub region: usize,
+    pub score: f64,
+}
+
+// Decomposition of walkable terrain into regions separated by chokepoints.
+//
+// Each walkable tile gets clearance: its distance to the nearest tile that cannot be walked on
+// (or map edge). Tiles are then flooded in order of decreasing clearance starting new region at
+// every local maximum (watershed). Where two regions meet, the meeting tile is the widest point of
+// the passage between them; if it is considerably narrower than both regions it becomes a
+// chokepoint, otherwise the regions are merged.
+//
+// Analysis only depends on terrain so it is meant to be done once per map and kept by the AI.
+#[derive(Debug, Clone)]
+pub struct TerrainAnalysis {
+    clearance: Vec<usize>,
+    regions: Vec<Option<usize>>,
+    region_count: usize,
+    chokepoints: Vec<Chokepoint>,
+}
+
+// Regions stay separate if passage between them is at most this fraction as wide as the smaller
+// of them
+const CHOKEPOINT_RATIO: f64 = 0.5;
+
+impl TerrainAnalysis {
+    pub fn new(terrain: &Terrain) -> TerrainAnalysis {
+        let clearance = TerrainAnalysis::compute_clearance(terrain);
+
+        let mut order = (0..terrain.size()).filter(|&index| clearance[index] > 0).collect::<Vec<_>>();
+        order.sort_by_key(|&index| (usize::MAX - clearance[index], index));
+
+        // Union-find over flooded basins; peak is the clearance of the local maximum basin grew from
+        let mut parents: Vec<usize> = Vec::new();
+        let mut peaks: Vec<usize> = Vec::new();
+        let mut basins: Vec<Option<usize>> = vec![None; terrain.size()];
+        let mut saddles = Vec::new();
+
+        fn find(parents: &mut [usize], basin: usize) -> usize {
+            let mut root = basin;
+            while parents[root] != root {
+                root = parents[root];
+            }
+            parents[basin] = root;
+            root
+        }
+
+        for index in order {
+            let location = terrain.location_by_index(index).expect("index out of terrain");
+            let mut neighbour_basins = location.neighbours_with(Connectivity::Four)
+                .filter_map(|(_direction, neighbour)| basins[neighbour.index()])
+                .map(|basin| find(&mut parents, basin))
+                .collect::<Vec<_>>();
+            neighbour_basins.sort();
+            neighbour_basins.dedup();
+
+            let basin = match neighbour_basins.first() {
+                None => {
+                    parents.push(parents.len());
+                    peaks.push(clearance[index]);
+                    parents.len() - 1
+                }
+                Some(&basin) => basin,
+            };
+            basins[index] = Some(basin);
+
+            for &other in neighbour_basins.iter().skip(1) {
+                let (basin, other) = (find(&mut parents, basin), find(&mut parents, other));
+                if basin == other {
+                    continue;
+                }
+
+                let smaller_peak = peaks[basin].min(peaks[other]);
+                if clearance[index] as f64 <= smaller_peak as f64 * CHOKEPOINT_RATIO {
+                    saddles.push((index, basin, other));
+                } else {
+                    parents[other] = basin;
+                    peaks[basin] = peaks[basin].max(peaks[other]);
+                }
+            }
+        }
+
+        // Number regions in order of their first tile
+        let mut region_ids = HashMap::new();
+        let mut regions = vec![None; terrain.size()];
+        for index in 0..terrain.size() {
+            if let Some(basin) = basins[index] {
+                let root = find(&mut parents, basin);
+                let next_id = region_ids.len();
+                regions[index] = Some(*region_ids.entry(root).or_insert(next_id));
+            }
+        }
+
+        // Keep widest passage between each pair of regions
+        let mut chokepoints: Vec<Chokepoint> = Vec::new();
+        for (index, basin, other) in saddles {
+            let a = region_ids[&find(&mut parents, basin)];
+            let b = region_ids[&find(&mut parents, other)];
+            if a == b {
+                continue;
+            }
+            let pair = (a.min(b), a.max(b));
+            if chokepoints.iter().any(|chokepoint| chokepoint.regions == pair) {
+                continue;
+            }
+            chokepoints.push(Chokepoint {
+                coordinates: terrain.coordinates(index).expect("index out of terrain"),
+                regions: pair,
+                clearance: clearance[index],
+            });
+        }
+
+        TerrainAnalysis {
+            clearance,
+            region_count: region_ids.len(),
+            regions,
+            chokepoints,
+        }
+    }
+
+    // Breadth first search from all tiles that cannot be walked on and from beyond the map edge
+    fn compute_clearance(terrain: &Terrain) -> Vec<usize> {
+        let mut clearance = vec![0; terrain.size()];
+        let mut queue = VecDeque::new();
+
+        for location in terrain.rows().flat_map(|row| row.into_iter()) {
+            let Coordinates(x, y) = location.coordinates;
+            if !location.walkable() {
+                continue;
+            }
+            if x == 0 || y == 0 || x == terrain.width() - 1 || y == terrain.height() - 1
+                || location.neighbours_with(Connectivity::Eight).any(|(_, neighbour)| !neighbour.walkable())
+            {
+                clearance[location.index()] = 1;
+                queue.push_back(location.index());
+            }
+        }
+
+        while let Some(index) = queue.pop_front() {
+            let location = terrain.location_by_index(index).expect("index out of terrain");
+            for (_direction, neighbour) in location.neighbours_with(Connectivity::Eight) {
+                if neighbour.walkable() && clearance[neighbour.index()] == 0 {
+                    clearance[neighbour.index()] = clearance[index] + 1;
+                    queue.push_back(neighbour.index());
+                }
+            }
+        }
+
+        clearance
+    }
+
+    pub fn region_count(&self) -> usize {
+        self.region_count
+    }
+
+    // None for tiles that cannot be walked on
+    pub fn region(&self, location: Location) -> Option<usize> {
+        self.regions.get(location.index()).and_then(|region| *region)
+    }
+
+    pub fn clearance(&self, location: Location) -> usize {
+        self.clearance.get(location.index()).cloned().unwrap_or(0)
+    }
+
+    pub fn chokepoints(&self) -> &[Chokepoint] {
+        &self.chokepoints
+    }
+
+    pub fn chokepoints_of(&self, region: usize) -> Vec<&Chokepoint> {
+        self.chokepoints.iter()
+            .filter(|chokepoint| chokepoint.regions.0 == region || chokepoint.regions.1 == region)
+            .collect()
+    }
+}
+
+impl<'p: 's, 'm: 's, 's> GameView<'p, 'm, 's> {
+    pub fn terrain_analysis(&self) -> TerrainAnalysis {
+        TerrainAnalysis::new(self.terrain())
+    }
+
+    // Free locations where a base could be built ordered from the best one. Sites are scored by
+    // amount of resources within radius weighted down with distance; locations right next to
+    // resources (leaving no room for workers) or within radius of existing base are skipped.
+    pub fn expansion_sites(&self, analysis: &TerrainAnalysis, radius: usize) -> Vec<ExpansionSite> {
+        let metric = self.connectivity().metric();
+
+        let mut resources = Vec::new();
+        let mut bases = Vec::new();
+        for (_entity_id, entity) in self.entities() {
+            match entity.object {
+                Object::Resources(Resource(amount)) => resources.push((entity.location, amount)),
+                Object::Building(_, Building::Base(_)) => bases.push(entity.location),
+                _ => (),
+            }
+        }
+
+        let mut sites = self.terrain().rows()
+            .flat_map(|row| row.into_iter())
+            .filter(|&location| location.buildable() && self.game.get_entity_by_location(location).is_none())
+            .filter(|&location| bases.iter().all(|base| self.distance(location, *base) > radius))
+            .filter_map(|location| {
+                let mut score = 0.0;
+                for &(resource, amount) in &resources {
+                    let distance = location.coordinates.distance(resource.coordinates, metric);
+                    if distance < 2 {
+                        return None;
+                    }
+                    if distance <= radius {
+                        score += amount as f64 / distance as f64;
+                    }
+                }
+
+                match analysis.region(location) {
+                    Some(region) if score > 0.0 => Some(ExpansionSite {
+                        coordinates: location.coordinates,
+                        region,
+                        score,
+                    }),
+                    _ => None,
+                }
+            })
+            .collect::<Vec<_>>();
+
+        sites.sort_by(|a, b| b.score.partial_cmp(&a.score).expect("score is NaN"));
+        sites
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use game::{TerrainBuilder, Dimension, Tile, Metric};
+    use game::test_support::TwoPlayers;
+
+    #[test]
+    fn terrain_analysis() {
+        // Two rooms connected by single tile gap
+        let mut terrain_builder = TerrainBuilder::new(Dimension::new(17).unwrap(), Dimension::new(7).unwrap());
+        for y in 0..7 {
+            if y != 3 {
+                terrain_builder = terrain_builder.place(Coordinates(8, y), Tile::Wall).unwrap();
+            }
+        }
+        let terrain = terrain_builder.build();
+        let players = TwoPlayers::on(terrain);
+        let p1 = &players.p1;
+
+        let game = players.game(&[
+            (Coordinates(2, 3), Object::Building(p1, Building::Base(Resource(10)))),
+            (Coordinates(0, 0), Object::Resources(Resource(10))),
+            (Coordinates(16, 6), Object::Resources(Resource(10))),
+            (Coordinates(16, 5), Object::Resources(Resource(10))),
+        ]);
+        let view = game.view_for(p1);
+        let location = |x, y| players.location(x, y);
+
+        let analysis = view.terrain_analysis();
+        assert_eq!(analysis.region_count(), 2);
+        assert_eq!(analysis.region(location(8, 0)), None);
+        assert!(analysis.region(location(1, 1)) != analysis.region(location(15, 5)));
+        assert_eq!(analysis.clearance(location(8, 3)), 1);
+        assert_eq!(analysis.clearance(location(3, 3)), 4);
+
+        let left = analysis.region(location(1, 1)).unwrap();
+        let right = analysis.region(location(15, 5)).unwrap();
+        assert_eq!(analysis.chokepoints().len(), 1);
+        assert_eq!(analysis.chokepoints_of(left), analysis.chokepoints_of(right));
+        assert_eq!(analysis.chokepoints()[0].coordinates.1, 3);
+        assert!((7..10).contains(&analysis.chokepoints()[0].coordinates.0));
+
+        // Resources next to the base are taken; best site is by the pair of resources, not next to them
+        let sites = view.expansion_sites(&analysis, 4);
+        let best = &sites[0];
+        assert_eq!(best.region, right);
+        assert!(best.coordinates.distance(Coordinates(16, 5), Metric::Manhattan) >= 2);
+        assert!(best.coordinates.distance(Coordinates(16, 6), Metric::Manhattan) >= 2);
+        assert!(sites.iter().all(|site| site.coordinates.distance(Coordinates(2, 3), Metric::Manhattan) > 4));
+    }
+}
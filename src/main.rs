@@ -1,28 +1,19 @@
 extern crate micro_rts;
-extern crate itertools;
 
-use itertools::interleave;
-
-use micro_rts::game::{Building, Colour, Coordinates, GameStateBuilder, TerrainBuilder, Object, Owned, Player,
-           Resource, Unit, AI};
+use micro_rts::game::{Building, Colour, Coordinates, GameStateBuilder, TerrainBuilder, Object, Player,
+           Resource, Unit};
 use micro_rts::ai::idle_ai::IdleAI;
 use micro_rts::ai::test_ai::TestAI;
+use micro_rts::match_runner::{Match, MatchConfig};
 
 fn main() {
     println!("Starting game");
 
-    let rounds = 1;
-    let cycles = 5;
-
     let terrain = TerrainBuilder::terrain_8x8_wall1();
 
     let p1 = Player::new("Mario", Colour::Red);
     let p2 = Player::new("Luigi", Colour::Green);
 
-    // not sure about state design
-    let mut p1_state = p1.new_state::<IdleAI>();
-    let mut p2_state = p2.new_state::<TestAI>();
-
     let mut game_builder = GameStateBuilder::new("foo", &terrain);
 
     game_builder
@@ -45,31 +36,24 @@ fn main() {
         .place(Coordinates(5, 5), Object::Unit(&p2, Unit::Worker))
         .unwrap();
 
-    for round in 0..rounds {
-        let mut game = game_builder.build_for_round(round);
-
-        println!("{}", game);
-
-        let mut p1_ai = p1.new_ai::<IdleAI>();
-        let mut p2_ai = p2.new_ai::<TestAI>();
-
-        for cycle in 0..cycles {
-            println!();
-            println!("Cycle: {}", cycle);
-
-            // Tag owner of desires
-            let p1_desires = p1_ai
-                .update(&mut p1_state, game.view_for(&p1))
-                .into_iter()
-                .map(|d| Owned(&p1, d));
-            let p2_desires = p2_ai
-                .update(&mut p2_state, game.view_for(&p2))
-                .into_iter()
-                .map(|d| Owned(&p2, d));
-
-            game.apply(interleave(p1_desires, p2_desires));
-
-            println!("{}", game);
+    let config = MatchConfig {
+        rounds: 1,
+        cycles: 5,
+        verbose: true,
+        ..Default::default()
+    };
+
+    let mut game_match = Match::new(&game_builder, config);
+    game_match
+        .player::<IdleAI>(&p1)
+        .player::<TestAI>(&p2);
+
+    for result in game_match.play() {
+        for overrun in &result.overruns {
+            println!("{} overran time budget in cycle {}: {:?}", overrun.player.name, overrun.cycle, overrun.elapsed);
+        }
+        for panic in &result.panics {
+            println!("{} AI panicked in cycle {}: {}", panic.player.name, panic.cycle, panic.message);
         }
     }
 }
@@ -0,0 +1,1064 @@
+use std::any::Any;
+use std::mem;
+use std::panic::{self, AssertUnwindSafe};
+use std::ptr;
+use std::sync::{Arc, Mutex};
+use std::sync::mpsc::{self, Sender, Receiver, RecvTimeoutError, TryRecvError};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+use game::{GameStateBuilder, Snapshot, Terrain, Player, Owned, Order, AI, AnytimeAI};
+use game::evaluation::{Evaluation, Material};
+use game_view::GameView;
+
+// Drives AI of one player through the match; persistent state is kept between rounds. Agents of
+// all players are updated at the same time on separate threads.
+pub trait Agent: Send {
+    // Called before every round
+    fn new_round(&mut self);
+
+    // Orders should be given before deadline; orders published to progress in the meantime are
+    // used instead if the agent overruns and the match accepts partial orders
+    fn update<'p: 'gs, 't: 'gs, 'gs>(
+        &mut self,
+        view: GameView<'p, 't, 'gs>,
+        deadline: Instant,
+        progress: &Progress,
+    ) -> Vec<Order>;
+}
+
+// Best orders an agent has come up with so far in the current update
+#[derive(Debug, Clone, Default)]
+pub struct Progress(Arc<Mutex<Option<Vec<Order>>>>);
+
+impl Progress {
+    pub fn publish(&self, orders: Vec<Order>) {
+        *self.0.lock().expect("progress lock poisoned") = Some(orders);
+    }
+
+    fn take(&self) -> Option<Vec<Order>> {
+        self.0.lock().expect("progress lock poisoned").take()
+    }
+}
+
+pub struct AIAgent<A: AI> {
+    ai: A,
+    state: A::PersistentState,
+}
+
+impl<A: AI> Default for AIAgent<A> {
+    fn default() -> AIAgent<A> {
+        AIAgent {
+            ai: A::default(),
+            state: A::PersistentState::default(),
+        }
+    }
+}
+
+impl<A: AI + Send> Agent for AIAgent<A>
+where
+    A::PersistentState: Send,
+{
+    fn new_round(&mut self) {
+        self.ai = A::default();
+    }
+
+    // Plain AI manages its own time and has nothing to publish before it is done
+    fn update<'p: 'gs, 't: 'gs, 'gs>(
+        &mut self,
+        view: GameView<'p, 't, 'gs>,
+        _deadline: Instant,
+        _progress: &Progress,
+    ) -> Vec<Order> {
+        self.ai.update(&mut self.state, view)
+    }
+}
+
+// How often anytime agent publishes its best orders while thinking
+const PUBLISH_INTERVAL: Duration = Duration::from_millis(10);
+
+// Lets anytime AI improve its orders until deadline; search is started once per round and carried
+// on from cycle to cycle
+pub struct AnytimeAgent<A: AnytimeAI> {
+    ai: A,
+    state: A::PersistentState,
+    // Cycle of the last update
+    cycle: Option<u64>,
+}
+
+impl<A: AnytimeAI> Default for AnytimeAgent<A> {
+    fn default() -> AnytimeAgent<A> {
+        AnytimeAgent {
+            ai: A::default(),
+            state: A::PersistentState::default(),
+            cycle: None,
+        }
+    }
+}
+
+impl<A: AnytimeAI + Send> Agent for AnytimeAgent<A>
+where
+    A::PersistentState: Send,
+{
+    fn new_round(&mut self) {
+        self.ai = A::default();
+        self.cycle = None;
+    }
+
+    fn update<'p: 'gs, 't: 'gs, 'gs>(
+        &mut self,
+        view: GameView<'p, 't, 'gs>,
+        deadline: Instant,
+        progress: &Progress,
+    ) -> Vec<Order> {
+        // Start again if this is not the cycle following the last one AI saw
+        if self.cycle.map(|cycle| cycle + 1) != Some(view.cycle()) {
+            self.ai.start(&mut self.state, &view);
+        }
+        self.cycle = Some(view.cycle());
+
+        // Think in slices and publish the best orders before each one
+        loop {
+            progress.publish(self.ai.best(&mut self.state, &view));
+            let slice_end = deadline.min(Instant::now() + PUBLISH_INTERVAL);
+            self.ai.improve(&mut self.state, &view, slice_end);
+            // AI that stops before its slice ends has nothing more to think about
+            if slice_end >= deadline || Instant::now() < slice_end {
+                break;
+            }
+        }
+        self.ai.best(&mut self.state, &view)
+    }
+}
+
+// What happens to a player whose AI did not give orders within the budget
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OverrunPolicy {
+    // Player does nothing this cycle
+    SkipCycle,
+    // Best orders the agent has published so far are applied; anytime agents publish while they
+    // think, plain AIs only give complete orders so the player does nothing
+    AcceptPartial,
+    // Player gives up the round and issues no more orders
+    Forfeit,
+}
+
+// What happens to a player whose AI panicked
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PanicPolicy {
+    // Player does nothing this cycle and AI is started afresh, as if for a new round, since its
+    // state may be left broken by the panic
+    NoOrders,
+    // Player gives up the round and issues no more orders
+    Forfeit,
+}
+
+#[derive(Debug, Clone)]
+pub struct MatchConfig {
+    pub rounds: u32,
+    // Cycle limit of a round after which the round is decided by evaluation
+    pub cycles: u64,
+    // Wall-clock time AI can take to give orders in one cycle
+    pub cycle_budget: Option<Duration>,
+    // Wall-clock time AI can take over the whole round
+    pub total_budget: Option<Duration>,
+    pub overrun: OverrunPolicy,
+    pub panic: PanicPolicy,
+    // Time anytime AI is given to think each cycle; it is cut short to fit within the budgets
+    pub thinking_time: Duration,
+    // Print game state, orders and rule violations every cycle
+    pub verbose: bool,
+}
+
+impl Default for MatchConfig {
+    fn default() -> MatchConfig {
+        MatchConfig {
+            rounds: 1,
+            cycles: 3000,
+            cycle_budget: Some(Duration::from_millis(100)),
+            total_budget: None,
+            overrun: OverrunPolicy::SkipCycle,
+            panic: PanicPolicy::NoOrders,
+            thinking_time: Duration::from_millis(80),
+            verbose: false,
+        }
+    }
+}
+
+// AI gave no orders within the budget in given cycle; elapsed is how long it was waited for
+#[derive(Debug, Clone)]
+pub struct Overrun<'p> {
+    pub player: &'p Player,
+    pub cycle: u64,
+    pub elapsed: Duration,
+}
+
+// AI panicked in given cycle
+#[derive(Debug, Clone)]
+pub struct Panic<'p> {
+    pub player: &'p Player,
+    pub cycle: u64,
+    pub message: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ForfeitReason {
+    CycleBudget(Duration),
+    TotalBudget(Duration),
+    Panic(String),
+    // AI was still thinking in an earlier round when this one started
+    Unresponsive,
+}
+
+#[derive(Debug, Clone)]
+pub struct Forfeit<'p> {
+    pub player: &'p Player,
+    pub cycle: u64,
+    pub reason: ForfeitReason,
+}
+
+#[derive(Debug, Clone)]
+pub struct RoundResult<'p> {
+    pub round: u32,
+    pub cycles: u64,
+    // None if round ended in a draw
+    pub winner: Option<&'p Player>,
+    pub overruns: Vec<Overrun<'p>>,
+    pub panics: Vec<Panic<'p>>,
+    pub forfeits: Vec<Forfeit<'p>>,
+}
+
+// Where an agent is between rounds
+enum Slot {
+    Ready(Box<dyn Agent>),
+    // Still thinking on the thread of an earlier round
+    Busy(JoinHandle<Box<dyn Agent>>),
+    // Given to a worker for the current round
+    Taken,
+}
+
+// Plays rounds of a game between players' AIs. Orders are waited for only until the budget runs
+// out; running AI cannot be interrupted, so one that is still thinking sits out the following
+// cycles until it returns, and one still thinking when the next round starts forfeits that round.
+// Setting agents up for a round is not timed. Panics in AI are caught so that a faulty AI loses
+// the cycle or the round but not the whole match; panic message is still printed by the panic
+// hook.
+pub struct Match<'p: 'b, 't: 'b, 'b> {
+    builder: &'b GameStateBuilder<'p, 't>,
+    agents: Vec<(&'p Player, Slot)>,
+    config: MatchConfig,
+}
+
+impl<'p: 'b, 't: 'b, 'b> Match<'p, 't, 'b> {
+    pub fn new(builder: &'b GameStateBuilder<'p, 't>, config: MatchConfig) -> Match<'p, 't, 'b> {
+        Match {
+            builder,
+            agents: Vec::new(),
+            config,
+        }
+    }
+
+    pub fn player<A>(&mut self, player: &'p Player) -> &mut Match<'p, 't, 'b>
+    where
+        A: AI + Send + 'static,
+        A::PersistentState: Send + 'static,
+    {
+        self.agent(player, Box::new(AIAgent::<A>::default()))
+    }
+
+    pub fn anytime_player<A>(&mut self, player: &'p Player) -> &mut Match<'p, 't, 'b>
+    where
+        A: AnytimeAI + Send + 'static,
+        A::PersistentState: Send + 'static,
+    {
+        self.agent(player, Box::new(AnytimeAgent::<A>::default()))
+    }
+
+    pub fn agent(&mut self, player: &'p Player, agent: Box<dyn Agent>) -> &mut Match<'p, 't, 'b> {
+        self.agents.push((player, Slot::Ready(agent)));
+        self
+    }
+
+    pub fn play(&mut self) -> Vec<RoundResult<'p>> {
+        (0..self.config.rounds).map(|round| self.play_round(round)).collect()
+    }
+
+    fn play_round(&mut self, round: u32) -> RoundResult<'p> {
+        let config = self.config.clone();
+        let mut game = self.builder.build_for_round(round);
+        let players = self.agents.iter().map(|&(player, _)| player).collect::<Vec<_>>();
+        let mut result = RoundResult {
+            round,
+            cycles: 0,
+            winner: None,
+            overruns: Vec::new(),
+            panics: Vec::new(),
+            forfeits: Vec::new(),
+        };
+
+        let mut used = vec![Duration::from_secs(0); self.agents.len()];
+        let mut playing = vec![true; self.agents.len()];
+
+        // Every agent gets its own thread for the round, working on copies of the game so that a
+        // thread of an AI that does not return can be left behind
+        let world = Arc::new(World {
+            terrain: game.terrain().clone(),
+            players: players.iter().map(|&player| player.clone()).collect(),
+        });
+        let mut workers = Vec::new();
+        for (index, &mut (player, ref mut slot)) in self.agents.iter_mut().enumerate() {
+            let agent = match mem::replace(slot, Slot::Taken) {
+                Slot::Ready(agent) => Some(agent),
+                Slot::Busy(thread) => if thread.is_finished() {
+                    Some(thread.join().expect("AI thread failed"))
+                } else {
+                    *slot = Slot::Busy(thread);
+                    None
+                },
+                Slot::Taken => unreachable!("agent of the previous round was not put back"),
+            };
+            workers.push(agent.map(|agent| Worker::spawn(world.clone(), index, agent)));
+            if workers[index].is_none() {
+                playing[index] = false;
+                result.forfeits.push(Forfeit { player, cycle: 0, reason: ForfeitReason::Unresponsive });
+            }
+        }
+
+        // Player whose AI cannot even be set up gives up the round
+        for worker in workers.iter().flatten() {
+            worker.run(Task::NewRound);
+        }
+        for (index, worker) in workers.iter().enumerate() {
+            if let Some((Err(message), _)) = worker.as_ref().map(Worker::outcome) {
+                let player = players[index];
+                result.panics.push(Panic { player, cycle: 0, message: message.clone() });
+                playing[index] = false;
+                result.forfeits.push(Forfeit { player, cycle: 0, reason: ForfeitReason::Panic(message) });
+            }
+        }
+
+        if config.verbose {
+            println!("{}", game);
+        }
+
+        for cycle in 0..config.cycles {
+            if playing.iter().filter(|&&playing| playing).count() < 2 && workers.len() > 1 {
+                break;
+            }
+
+            // All AIs think at the same time on their own copies of the game
+            let snapshot = Arc::new(game.snapshot(&players));
+            let mut updates = vec![None; workers.len()];
+            for (index, worker) in workers.iter_mut().enumerate() {
+                let worker = match *worker {
+                    Some(ref mut worker) if playing[index] => worker,
+                    _ => continue,
+                };
+
+                // Orders given after the deadline are discarded; AI still thinking sits this
+                // cycle out
+                if let Some(counted) = worker.late {
+                    let (update, elapsed) = match worker.outcomes.try_recv() {
+                        Ok(outcome) => outcome,
+                        Err(TryRecvError::Empty) => continue,
+                        Err(TryRecvError::Disconnected) => panic!("AI thread failed"),
+                    };
+                    used[index] += elapsed.checked_sub(counted).unwrap_or_default();
+                    worker.late = None;
+                    if let Err(message) = update {
+                        if !handle_panic(&config, &mut result, worker, players[index], cycle, message) {
+                            playing[index] = false;
+                            continue;
+                        }
+                    }
+                }
+
+                // Budget left for this update; some of it is left for anytime AI to return its
+                // orders
+                let total_left = config.total_budget.map(|budget| budget.checked_sub(used[index]).unwrap_or_default());
+                let allowance = match (config.cycle_budget, total_left) {
+                    (Some(budget), Some(left)) => Some(budget.min(left)),
+                    (budget, left) => budget.or(left),
+                };
+                let mut thinking_time = config.thinking_time;
+                if let Some(allowance) = allowance {
+                    thinking_time = thinking_time.min(allowance * 9 / 10);
+                }
+
+                worker.progress.take();
+                worker.run(Task::Update(snapshot.clone(), thinking_time));
+                updates[index] = Some((Instant::now(), allowance));
+            }
+
+            let mut desires = Vec::new();
+            for (index, worker) in workers.iter_mut().enumerate() {
+                let player = players[index];
+                let (worker, (started, allowance)) = match (worker.as_mut(), updates[index]) {
+                    (Some(worker), Some(update)) => (worker, update),
+                    _ => {
+                        desires.push(Vec::new());
+                        continue;
+                    }
+                };
+
+                let orders = match worker.outcome_within(started, allowance) {
+                    Some((Ok(orders), elapsed)) => {
+                        used[index] += elapsed;
+                        orders
+                    }
+                    Some((Err(message), elapsed)) => {
+                        used[index] += elapsed;
+                        if !handle_panic(&config, &mut result, worker, player, cycle, message) {
+                            playing[index] = false;
+                        }
+                        Vec::new()
+                    }
+                    None => {
+                        let waited = allowance.expect("only updates with a budget time out");
+                        used[index] += waited;
+                        worker.late = Some(waited);
+                        result.overruns.push(Overrun { player, cycle, elapsed: waited });
+                        match config.overrun {
+                            OverrunPolicy::SkipCycle => Vec::new(),
+                            OverrunPolicy::AcceptPartial => worker.progress.take().unwrap_or_default(),
+                            OverrunPolicy::Forfeit => {
+                                let reason = match config.cycle_budget {
+                                    Some(budget) if budget <= waited => ForfeitReason::CycleBudget(waited),
+                                    _ => ForfeitReason::TotalBudget(used[index]),
+                                };
+                                playing[index] = false;
+                                result.forfeits.push(Forfeit { player, cycle, reason });
+                                Vec::new()
+                            }
+                        }
+                    }
+                };
+                desires.push(orders.into_iter().map(|order| Owned(player, order)).collect());
+            }
+
+            // Take orders of players in turns starting with a different player every cycle so
+            // that no player always goes first
+            let desires = interleave(desires, cycle as usize);
+
+            if config.verbose {
+                println!();
+                println!("Cycle: {}", cycle);
+                for &Owned(player, ref desire) in &desires {
+                    println!("{}: {:?}", player.name, desire);
+                }
+            }
+
+            for violation in game.apply(desires.into_iter()) {
+                if config.verbose {
+                    println!("Rule violation: {:?}", violation);
+                }
+            }
+
+            if config.verbose {
+                println!("{}", game);
+            }
+        }
+
+        // Agents that are done are taken back for the next round, the others are left thinking
+        for (&mut (_, ref mut slot), worker) in self.agents.iter_mut().zip(workers) {
+            if let Some(worker) = worker {
+                *slot = worker.finish();
+            }
+        }
+
+        result.cycles = game.cycle();
+
+        // Last player standing wins, otherwise the one ahead on material
+        let remaining = players.iter()
+            .zip(&playing)
+            .filter(|&(_, &playing)| playing)
+            .map(|(&player, _)| player)
+            .collect::<Vec<_>>();
+
+        result.winner = if remaining.len() == 1 {
+            Some(remaining[0])
+        } else {
+            let scores = remaining.iter()
+                .map(|&player| (player, Material.evaluate(&game.view_for(player))))
+                .collect::<Vec<_>>();
+            let best = scores.iter().map(|&(_, score)| score).fold(f64::NEG_INFINITY, f64::max);
+            let mut leaders = scores.iter().filter(|&&(_, score)| score == best);
+            match (leaders.next(), leaders.next()) {
+                (Some(&(player, _)), None) => Some(player),
+                _ => None,
+            }
+        };
+
+        if config.verbose {
+            match result.winner {
+                Some(winner) => println!("Round {} won by {}", round, winner.name),
+                None => println!("Round {} is a draw", round),
+            }
+        }
+
+        result
+    }
+}
+
+// Records panic of player's AI and applies the panic policy; tells whether the player goes on
+fn handle_panic<'p>(
+    config: &MatchConfig,
+    result: &mut RoundResult<'p>,
+    worker: &Worker,
+    player: &'p Player,
+    cycle: u64,
+    message: String,
+) -> bool {
+    result.panics.push(Panic { player, cycle, message: message.clone() });
+    let forfeit = match config.panic {
+        PanicPolicy::Forfeit => Some(message),
+        // Agent that panics again while being reset cannot go on
+        PanicPolicy::NoOrders => {
+            worker.run(Task::NewRound);
+            match worker.outcome() {
+                (Ok(_), _) => None,
+                (Err(message), _) => {
+                    result.panics.push(Panic { player, cycle, message: message.clone() });
+                    Some(message)
+                }
+            }
+        }
+    };
+    match forfeit {
+        Some(message) => {
+            result.forfeits.push(Forfeit { player, cycle, reason: ForfeitReason::Panic(message) });
+            false
+        }
+        None => true,
+    }
+}
+
+// Copies of terrain and players that worker threads restore snapshots of the game with
+struct World {
+    terrain: Terrain,
+    players: Vec<Player>,
+}
+
+enum Task {
+    NewRound,
+    // Update on given game with given thinking time
+    Update(Arc<Snapshot>, Duration),
+}
+
+// Orders or panic message of a task together with the time it took
+type Outcome = (Result<Vec<Order>, String>, Duration);
+
+// Thread running tasks of one agent; it ends and gives the agent back when the worker is dropped
+// and the task it is running is done
+struct Worker {
+    tasks: Sender<Task>,
+    outcomes: Receiver<Outcome>,
+    progress: Progress,
+    thread: JoinHandle<Box<dyn Agent>>,
+    // Time counted so far for an update that went past its deadline; worker is busy until its
+    // outcome arrives
+    late: Option<Duration>,
+}
+
+impl Worker {
+    // Agent plays as player at given position in the world's players
+    fn spawn(world: Arc<World>, player: usize, mut agent: Box<dyn Agent>) -> Worker {
+        let (tasks, task_receiver) = mpsc::channel();
+        let (outcome_sender, outcomes) = mpsc::channel();
+        let progress = Progress::default();
+        let published = progress.clone();
+
+        let thread = thread::spawn(move || {
+            let players = world.players.iter().collect::<Vec<_>>();
+            for task in task_receiver {
+                let started = Instant::now();
+                let outcome = match task {
+                    Task::NewRound => panic::catch_unwind(AssertUnwindSafe(|| {
+                        agent.new_round();
+                        Vec::new()
+                    })),
+                    Task::Update(snapshot, thinking_time) => {
+                        let game = snapshot.restore(&world.terrain, &players);
+                        panic::catch_unwind(AssertUnwindSafe(|| {
+                            agent.update(game.view_for(players[player]), started + thinking_time, &published)
+                        }))
+                    }
+                };
+                if outcome_sender.send((outcome.map_err(panic_message), started.elapsed())).is_err() {
+                    break;
+                }
+            }
+            agent
+        });
+
+        Worker { tasks, outcomes, progress, thread, late: None }
+    }
+
+    fn run(&self, task: Task) {
+        self.tasks.send(task).expect("AI thread failed");
+    }
+
+    // Waits for outcome of the task that was run
+    fn outcome(&self) -> Outcome {
+        self.outcomes.recv().expect("AI thread failed")
+    }
+
+    // Waits for outcome of the task started at given time for at most allowance; None if it is
+    // not done by then
+    fn outcome_within(&self, started: Instant, allowance: Option<Duration>) -> Option<Outcome> {
+        let allowance = match allowance {
+            Some(allowance) => allowance,
+            None => return Some(self.outcome()),
+        };
+        match self.outcomes.recv_timeout((started + allowance).saturating_duration_since(Instant::now())) {
+            Ok(outcome) => Some(outcome),
+            Err(RecvTimeoutError::Timeout) => None,
+            Err(RecvTimeoutError::Disconnected) => panic!("AI thread failed"),
+        }
+    }
+
+    // Agent back if the worker is idle, otherwise its thread to collect the agent from later
+    fn finish(self) -> Slot {
+        let Worker { tasks, thread, late, .. } = self;
+        drop(tasks);
+        match late {
+            None => Slot::Ready(thread.join().expect("AI thread failed")),
+            Some(_) => Slot::Busy(thread),
+        }
+    }
+}
+
+fn panic_message(payload: Box<dyn Any + Send>) -> String {
+    match payload.downcast::<String>() {
+        Ok(message) => *message,
+        Err(payload) => match payload.downcast::<&'static str>() {
+            Ok(message) => message.to_string(),
+            Err(_) => "unknown panic".to_string(),
+        },
+    }
+}
+
+// Takes one item from each list in turn, beginning with list at index first
+fn interleave<T>(lists: Vec<Vec<T>>, first: usize) -> Vec<T> {
+    let mut iters = lists.into_iter().map(|list| list.into_iter()).collect::<Vec<_>>();
+    if !iters.is_empty() {
+        let first = first % iters.len();
+        iters.rotate_left(first);
+    }
+    let mut interleaved = Vec::new();
+    loop {
+        let mut any = false;
+        for iter in &mut iters {
+            if let Some(item) = iter.next() {
+                interleaved.push(item);
+                any = true;
+            }
+        }
+        if !any {
+            return interleaved;
+        }
+    }
+}
+
+impl<'p> RoundResult<'p> {
+    pub fn is_winner(&self, player: &Player) -> bool {
+        self.winner.map(|winner| ptr::eq(winner, player)).unwrap_or(false)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::thread;
+    use std::sync::{Mutex, Condvar};
+    use std::sync::atomic::{AtomicU32, Ordering};
+    use game::{Coordinates, Object, Unit, Direction, EmptyPersistentState};
+    use game::test_support::TwoPlayers;
+    use ai::idle_ai::IdleAI;
+
+    // Time SlowAI takes every cycle; budgets in tests are far enough from it that scheduling delays
+    // do not change results
+    const SLOW: Duration = Duration::from_millis(50);
+
+    // Moves its units right, slowly
+    #[derive(Default)]
+    struct SlowAI;
+
+    impl AI for SlowAI {
+        type PersistentState = EmptyPersistentState;
+        fn update<'p: 'gs, 's: 'gs, 't: 'gs, 'gs>(
+            &mut self,
+            _sate: &'s mut Self::PersistentState,
+            view: GameView<'p, 't, 'gs>,
+        ) -> Vec<Order> {
+            thread::sleep(SLOW);
+            view.my_units().map(|unit| Order::Move(unit.entity_id, Direction::Right)).collect()
+        }
+    }
+
+    // Thinks a fixed number of steps over the round, slowly
+    #[derive(Default)]
+    struct Pondering {
+        starts: u32,
+        steps: u32,
+        limit: u32,
+    }
+
+    impl AnytimeAI for Pondering {
+        type PersistentState = EmptyPersistentState;
+
+        fn start(&mut self, _sate: &mut Self::PersistentState, _view: &GameView) {
+            self.starts += 1;
+            self.steps = 0;
+        }
+
+        fn improve(&mut self, _sate: &mut Self::PersistentState, _view: &GameView, deadline: Instant) {
+            while Instant::now() < deadline && self.steps < self.limit {
+                thread::sleep(Duration::from_millis(2));
+                self.steps += 1;
+            }
+        }
+
+        fn best(&mut self, _sate: &mut Self::PersistentState, view: &GameView) -> Vec<Order> {
+            view.my_units().map(|unit| Order::Move(unit.entity_id, Direction::Right)).collect()
+        }
+    }
+
+    #[test]
+    fn anytime_agent() {
+        let players = TwoPlayers::default();
+        let p1 = &players.p1;
+
+        let builder = players.builder(&[(Coordinates(0, 0), Object::Unit(p1, Unit::Worker))]);
+        let mut game = builder.build_for_round(0);
+
+        // Stops thinking at deadline
+        let mut agent = AnytimeAgent::<Pondering>::default();
+        let progress = Progress::default();
+        agent.ai.limit = 1000;
+        let orders = agent.update(game.view_for(p1), Instant::now() + Duration::from_millis(10), &progress);
+        assert!(agent.ai.steps >= 1 && agent.ai.steps < agent.ai.limit);
+        assert_eq!(orders.len(), 1);
+        assert_eq!(progress.take(), Some(orders));
+
+        // and carries on in the next cycle
+        let steps = agent.ai.steps;
+        game.apply(Vec::new().into_iter());
+        agent.update(game.view_for(p1), Instant::now() + Duration::from_millis(10), &progress);
+        assert_eq!(agent.ai.starts, 1);
+        assert!(agent.ai.steps > steps);
+
+        // or stops when it has nothing more to think about
+        agent.ai.limit = agent.ai.steps + 3;
+        game.apply(Vec::new().into_iter());
+        agent.update(game.view_for(p1), Instant::now() + Duration::from_secs(10), &progress);
+        assert_eq!(agent.ai.starts, 1);
+        assert_eq!(agent.ai.steps, agent.ai.limit);
+
+        // Search starts again when the game is not the one of the next cycle
+        agent.update(game.view_for(p1), Instant::now(), &progress);
+        assert_eq!(agent.ai.starts, 2);
+
+        agent.new_round();
+        agent.update(builder.build_for_round(1).view_for(p1), Instant::now(), &progress);
+        assert_eq!(agent.ai.starts, 1);
+    }
+
+    // Panics in every other cycle, telling how many times it was updated
+    #[derive(Default)]
+    struct FaultyAI {
+        updates: u32,
+    }
+
+    impl AI for FaultyAI {
+        type PersistentState = EmptyPersistentState;
+        fn update<'p: 'gs, 's: 'gs, 't: 'gs, 'gs>(
+            &mut self,
+            _sate: &'s mut Self::PersistentState,
+            view: GameView<'p, 't, 'gs>,
+        ) -> Vec<Order> {
+            self.updates += 1;
+            if view.cycle().is_multiple_of(2) {
+                panic!("faulty update {} in cycle {}", self.updates, view.cycle());
+            }
+            view.my_units().map(|unit| Order::Move(unit.entity_id, Direction::Right)).collect()
+        }
+    }
+
+    #[test]
+    fn panic_policies() {
+        let players = TwoPlayers::default();
+        let (p1, p2) = (&players.p1, &players.p2);
+
+        let builder = players.builder(&[
+            (Coordinates(0, 0), Object::Unit(p1, Unit::Worker)),
+            (Coordinates(0, 7), Object::Unit(p2, Unit::Worker)),
+        ]);
+
+        let play = |panic| {
+            let config = MatchConfig {
+                cycles: 4,
+                panic,
+                ..Default::default()
+            };
+            let mut game_match = Match::new(&builder, config);
+            game_match.player::<FaultyAI>(p1).player::<IdleAI>(p2);
+            game_match.play().remove(0)
+        };
+
+        let result = play(PanicPolicy::NoOrders);
+        assert_eq!(result.panics.iter().map(|panic| panic.cycle).collect::<Vec<_>>(), vec![0, 2]);
+        assert_eq!(result.panics[0].message, "faulty update 1 in cycle 0");
+        // AI was reset after the first panic
+        assert_eq!(result.panics[1].message, "faulty update 2 in cycle 2");
+        assert!(result.forfeits.is_empty());
+        assert_eq!(result.cycles, 4);
+
+        let result = play(PanicPolicy::Forfeit);
+        assert_eq!(result.panics.len(), 1);
+        match result.forfeits.as_slice() {
+            &[Forfeit { cycle: 0, reason: ForfeitReason::Panic(ref message), .. }] => {
+                assert_eq!(message, "faulty update 1 in cycle 0")
+            }
+            forfeits => panic!("unexpected forfeits: {:?}", forfeits),
+        }
+        assert!(result.is_winner(p2));
+
+        // Agent that panics when set up for the round does not get to play it
+        struct BrokenAgent;
+
+        impl Agent for BrokenAgent {
+            fn new_round(&mut self) {
+                panic!("broken agent");
+            }
+
+            fn update<'p: 'gs, 't: 'gs, 'gs>(
+                &mut self,
+                _view: GameView<'p, 't, 'gs>,
+                _deadline: Instant,
+                _progress: &Progress,
+            ) -> Vec<Order> {
+                Vec::new()
+            }
+        }
+
+        let mut game_match = Match::new(&builder, MatchConfig { cycles: 4, ..Default::default() });
+        game_match.agent(p1, Box::new(BrokenAgent)).player::<IdleAI>(p2);
+        let result = game_match.play().remove(0);
+        assert_eq!(result.panics.len(), 1);
+        assert_eq!(result.forfeits[0].reason, ForfeitReason::Panic("broken agent".to_string()));
+        assert_eq!(result.cycles, 0);
+        assert!(result.is_winner(p2));
+    }
+
+    // Number of MeetingAI updates so far
+    static ARRIVALS: Mutex<u32> = Mutex::new(0);
+    static ARRIVED: Condvar = Condvar::new();
+    // Number of MeetingAI updates that waited in vain
+    static MISSED: AtomicU32 = AtomicU32::new(0);
+
+    // Waits at a barrier that both players' MeetingAIs have to reach in every cycle; barrier gives
+    // up after a while so that updating AIs one after the other fails instead of hanging
+    #[derive(Default)]
+    struct MeetingAI;
+
+    impl AI for MeetingAI {
+        type PersistentState = EmptyPersistentState;
+        fn update<'p: 'gs, 's: 'gs, 't: 'gs, 'gs>(
+            &mut self,
+            _sate: &'s mut Self::PersistentState,
+            view: GameView<'p, 't, 'gs>,
+        ) -> Vec<Order> {
+            let mut arrivals = ARRIVALS.lock().unwrap();
+            *arrivals += 1;
+            let meeting = arrivals.div_ceil(2) * 2;
+            ARRIVED.notify_all();
+
+            let timeout = Duration::from_secs(10);
+            let (_arrivals, wait) = ARRIVED
+                .wait_timeout_while(arrivals, timeout, |arrivals| *arrivals < meeting)
+                .unwrap();
+            if wait.timed_out() {
+                MISSED.fetch_add(1, Ordering::SeqCst);
+                return Vec::new();
+            }
+            view.my_units().map(|unit| Order::Move(unit.entity_id, Direction::Right)).collect()
+        }
+    }
+
+    #[test]
+    fn agents_run_concurrently() {
+        let players = TwoPlayers::default();
+        let (p1, p2) = (&players.p1, &players.p2);
+
+        let builder = players.builder(&[
+            (Coordinates(0, 0), Object::Unit(p1, Unit::Worker)),
+            (Coordinates(0, 7), Object::Unit(p2, Unit::Worker)),
+        ]);
+
+        let config = MatchConfig {
+            cycles: 3,
+            cycle_budget: None,
+            ..Default::default()
+        };
+        let mut game_match = Match::new(&builder, config);
+        game_match.player::<MeetingAI>(p1).player::<MeetingAI>(p2);
+
+        // AIs only meet if they are updated at the same time
+        let result = game_match.play().remove(0);
+        assert_eq!(result.cycles, 3);
+        assert_eq!(*ARRIVALS.lock().unwrap(), 6);
+        assert_eq!(MISSED.load(Ordering::SeqCst), 0);
+    }
+
+    #[test]
+    fn interleave_rotates_first_list() {
+        assert_eq!(interleave(vec![vec![1, 2], vec![3], vec![4, 5]], 0), vec![1, 3, 4, 2, 5]);
+        assert_eq!(interleave(vec![vec![1, 2], vec![3], vec![4, 5]], 1), vec![3, 4, 1, 5, 2]);
+        assert_eq!(interleave(vec![vec![1, 2], vec![3], vec![4, 5]], 5), vec![4, 1, 3, 5, 2]);
+    }
+
+    #[test]
+    fn overrun_policies() {
+        let players = TwoPlayers::default();
+        let (p1, p2) = (&players.p1, &players.p2);
+
+        let builder = players.builder(&[
+            (Coordinates(0, 0), Object::Unit(p1, Unit::Worker)),
+            (Coordinates(0, 7), Object::Unit(p2, Unit::Worker)),
+        ]);
+
+        let play = |overrun, cycle_budget, total_budget| {
+            let config = MatchConfig {
+                cycles: 3,
+                cycle_budget,
+                total_budget,
+                overrun,
+                ..Default::default()
+            };
+            let mut game_match = Match::new(&builder, config);
+            game_match.player::<SlowAI>(p1).player::<IdleAI>(p2);
+            game_match.play().remove(0)
+        };
+
+        let cycle_budget = Some(SLOW / 2);
+
+        // AI still thinking after the first overrun sits out the remaining cycles
+        let result = play(OverrunPolicy::SkipCycle, cycle_budget, None);
+        assert_eq!(result.overruns.len(), 1);
+        assert!(result.forfeits.is_empty());
+        assert_eq!(result.cycles, 3);
+        assert!(result.winner.is_none());
+
+        // Plain AI has no partial orders to give
+        let result = play(OverrunPolicy::AcceptPartial, cycle_budget, None);
+        assert_eq!(result.overruns.len(), 1);
+        assert!(result.forfeits.is_empty());
+        assert!(result.winner.is_none());
+
+        let result = play(OverrunPolicy::Forfeit, cycle_budget, None);
+        assert_eq!(result.overruns.len(), 1);
+        match result.forfeits.as_slice() {
+            &[Forfeit { cycle: 0, reason: ForfeitReason::CycleBudget(_), .. }] => (),
+            forfeits => panic!("unexpected forfeits: {:?}", forfeits),
+        }
+        assert_eq!(result.cycles, 1);
+        assert!(result.is_winner(p2));
+
+        // Fits in each cycle but not in total
+        let result = play(OverrunPolicy::Forfeit, None, Some(SLOW * 3 / 2));
+        match result.forfeits.as_slice() {
+            &[Forfeit { cycle: 1, reason: ForfeitReason::TotalBudget(_), .. }] => (),
+            forfeits => panic!("unexpected forfeits: {:?}", forfeits),
+        }
+    }
+
+    // Time Stubborn takes to think; much longer than the matches that leave it behind
+    const HANG: Duration = Duration::from_millis(1000);
+
+    // Anytime AI that wants to move its units right but ignores deadlines while thinking
+    #[derive(Default)]
+    struct Stubborn;
+
+    impl AnytimeAI for Stubborn {
+        type PersistentState = EmptyPersistentState;
+
+        fn start(&mut self, _sate: &mut Self::PersistentState, _view: &GameView) {}
+
+        fn improve(&mut self, _sate: &mut Self::PersistentState, _view: &GameView, _deadline: Instant) {
+            thread::sleep(HANG);
+        }
+
+        fn best(&mut self, _sate: &mut Self::PersistentState, view: &GameView) -> Vec<Order> {
+            view.my_units().map(|unit| Order::Move(unit.entity_id, Direction::Right)).collect()
+        }
+    }
+
+    // X coordinates of enemy units Watcher has seen
+    static WATCHED: Mutex<Vec<usize>> = Mutex::new(Vec::new());
+
+    #[derive(Default)]
+    struct Watcher;
+
+    impl AI for Watcher {
+        type PersistentState = EmptyPersistentState;
+        fn update<'p: 'gs, 's: 'gs, 't: 'gs, 'gs>(
+            &mut self,
+            _sate: &'s mut Self::PersistentState,
+            view: GameView<'p, 't, 'gs>,
+        ) -> Vec<Order> {
+            let mut watched = WATCHED.lock().unwrap();
+            watched.extend(
+                view.entities()
+                    .filter(|&(_, entity)| !view.is_mine(entity))
+                    .map(|(_, entity)| entity.location.coordinates.0)
+            );
+            Vec::new()
+        }
+    }
+
+    #[test]
+    fn hanging_ai_does_not_stall_match() {
+        let players = TwoPlayers::default();
+        let (p1, p2) = (&players.p1, &players.p2);
+
+        let builder = players.builder(&[
+            (Coordinates(0, 0), Object::Unit(p1, Unit::Worker)),
+            (Coordinates(0, 7), Object::Unit(p2, Unit::Worker)),
+        ]);
+
+        let play = |overrun| {
+            let config = MatchConfig {
+                rounds: 2,
+                cycles: 3,
+                cycle_budget: Some(SLOW),
+                overrun,
+                ..Default::default()
+            };
+            WATCHED.lock().unwrap().clear();
+            let started = Instant::now();
+            let mut game_match = Match::new(&builder, config);
+            game_match.anytime_player::<Stubborn>(p1).player::<Watcher>(p2);
+            let results = game_match.play();
+            assert!(started.elapsed() < HANG);
+            (results, WATCHED.lock().unwrap().clone())
+        };
+
+        // Orders published before AI got stuck are applied at the deadline
+        let (results, watched) = play(OverrunPolicy::AcceptPartial);
+        assert_eq!(results[0].overruns.len(), 1);
+        assert_eq!(results[0].cycles, 3);
+        assert_eq!(&watched[..3], &[0, 1, 1]);
+
+        // AI still thinking at the start of the next round forfeits it
+        match results[1].forfeits.as_slice() {
+            &[Forfeit { cycle: 0, reason: ForfeitReason::Unresponsive, .. }] => (),
+            forfeits => panic!("unexpected forfeits: {:?}", forfeits),
+        }
+        assert!(results[1].is_winner(p2));
+
+        let (results, watched) = play(OverrunPolicy::SkipCycle);
+        assert_eq!(results[0].overruns.len(), 1);
+        assert_eq!(&watched[..3], &[0, 0, 0]);
+    }
+}
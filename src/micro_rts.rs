@@ -1,7 +1,9 @@
 extern crate ansi_term;
 extern crate itertools;
 extern crate pathfinding;
+extern crate rand;
 
 pub mod game;
 pub mod game_view;
-pub mod ai;
\ No newline at end of file
+pub mod ai;
+pub mod match_runner;
\ No newline at end of file